@@ -0,0 +1,242 @@
+// Copyright Materialize, Inc. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License in the LICENSE file at the
+// root of this repository, or online at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Memory allocation.
+//!
+//! By default, libsasl2 allocates memory with the C library's `malloc`.
+//! [`RustAllocator`] makes it use Rust's global allocator instead, so that
+//! its memory is visible to whatever profiling or accounting the global
+//! allocator performs.
+//!
+//! libsasl2 only accepts allocation functions before it is first initialized,
+//! so the allocator must be installed before any connection or
+//! [`SaslContext`](crate::context::SaslContext) is created.
+//!
+//! ```no_run
+//! use sasl2::allocator::RustAllocator;
+//!
+//! RustAllocator::new().zeroize_on_free(true).install()?;
+//! # Ok::<(), sasl2::error::SaslError>(())
+//! ```
+
+use std::alloc::{self, Layout};
+use std::cmp;
+use std::ptr;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use libc::c_void;
+use sasl2_sys::sasl::sasl_set_alloc;
+
+use crate::context;
+use crate::error::SaslError;
+
+/// The size of the header that precedes each allocation and records its
+/// size. This is also the alignment of each allocation, which matches the
+/// alignment guaranteed by `malloc` on common platforms.
+const HEADER: usize = 16;
+
+static INSTALLED: AtomicBool = AtomicBool::new(false);
+static ZEROIZE: AtomicBool = AtomicBool::new(false);
+
+/// An installer for allocation functions that route libsasl2's allocations
+/// through Rust's global allocator (`sasl_set_alloc`).
+#[derive(Debug, Clone, Default)]
+pub struct RustAllocator {
+    zeroize: bool,
+}
+
+impl RustAllocator {
+    /// Creates an installer with the default settings.
+    pub fn new() -> RustAllocator {
+        RustAllocator::default()
+    }
+
+    /// Declares whether memory is overwritten with zeros when libsasl2 frees
+    /// or reallocates it.
+    ///
+    /// This wipes secrets that libsasl2 and its plugins copy internally, like
+    /// passwords and session keys, at the cost of touching every byte that is
+    /// freed.
+    pub fn zeroize_on_free(mut self, zeroize: bool) -> RustAllocator {
+        self.zeroize = zeroize;
+        self
+    }
+
+    /// Installs the allocation functions.
+    ///
+    /// Returns a [`SaslError::BadParam`] error if the allocation functions
+    /// have already been installed or the library has already been
+    /// initialized.
+    pub fn install(self) -> Result<(), SaslError> {
+        context::configure(|| {
+            if INSTALLED.swap(true, Ordering::SeqCst) {
+                return Err(SaslError::bad_param("allocator already installed"));
+            }
+            ZEROIZE.store(self.zeroize, Ordering::SeqCst);
+            unsafe {
+                sasl_set_alloc(
+                    Some(sasl_malloc),
+                    Some(sasl_calloc),
+                    Some(sasl_realloc),
+                    Some(sasl_free),
+                );
+            }
+            Ok(())
+        })
+    }
+}
+
+fn layout(size: usize) -> Option<Layout> {
+    let size = size.checked_add(HEADER)?;
+    Layout::from_size_align(size, HEADER).ok()
+}
+
+/// Allocates `size` bytes, preceded by a header that records `size`.
+unsafe fn allocate(size: usize, zeroed: bool) -> *mut u8 {
+    let layout = match layout(size) {
+        Some(layout) => layout,
+        None => return ptr::null_mut(),
+    };
+    let base = if zeroed {
+        alloc::alloc_zeroed(layout)
+    } else {
+        alloc::alloc(layout)
+    };
+    if base.is_null() {
+        return base;
+    }
+    (base as *mut usize).write(size);
+    base.add(HEADER)
+}
+
+/// Returns the base and size of an allocation made by [`allocate`].
+unsafe fn allocation(ptr: *mut u8) -> (*mut u8, usize) {
+    let base = ptr.sub(HEADER);
+    (base, (base as *const usize).read())
+}
+
+unsafe fn zeroize(ptr: *mut u8, size: usize) {
+    for i in 0..size {
+        ptr::write_volatile(ptr.add(i), 0);
+    }
+}
+
+unsafe extern "C" fn sasl_malloc(size: usize) -> *mut c_void {
+    allocate(size, false) as *mut c_void
+}
+
+unsafe extern "C" fn sasl_calloc(count: usize, size: usize) -> *mut c_void {
+    match count.checked_mul(size) {
+        Some(size) => allocate(size, true) as *mut c_void,
+        None => ptr::null_mut(),
+    }
+}
+
+unsafe extern "C" fn sasl_realloc(ptr: *mut c_void, size: usize) -> *mut c_void {
+    let ptr = ptr as *mut u8;
+    if ptr.is_null() {
+        return allocate(size, false) as *mut c_void;
+    }
+    let (base, old_size) = allocation(ptr);
+    if ZEROIZE.load(Ordering::Relaxed) {
+        // Reallocating in place could leave a copy of the data behind, so
+        // always move it to a new allocation and wipe the old one.
+        let new = allocate(size, false);
+        if new.is_null() {
+            return ptr::null_mut();
+        }
+        ptr::copy_nonoverlapping(ptr, new, cmp::min(old_size, size));
+        sasl_free(ptr as *mut c_void);
+        return new as *mut c_void;
+    }
+    let new_size = match layout(size) {
+        Some(layout) => layout.size(),
+        None => return ptr::null_mut(),
+    };
+    let base = alloc::realloc(base, layout(old_size).unwrap(), new_size);
+    if base.is_null() {
+        return ptr::null_mut();
+    }
+    (base as *mut usize).write(size);
+    base.add(HEADER) as *mut c_void
+}
+
+unsafe extern "C" fn sasl_free(ptr: *mut c_void) {
+    let ptr = ptr as *mut u8;
+    if ptr.is_null() {
+        return;
+    }
+    let (base, size) = allocation(ptr);
+    if ZEROIZE.load(Ordering::Relaxed) {
+        zeroize(ptr, size);
+    }
+    alloc::dealloc(base, layout(size).unwrap());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    unsafe fn bytes<'a>(ptr: *mut c_void, len: usize) -> &'a mut [u8] {
+        std::slice::from_raw_parts_mut(ptr as *mut u8, len)
+    }
+
+    // The zeroize setting is process-global, so both settings are exercised
+    // from a single test.
+    #[test]
+    fn test_hooks() {
+        unsafe {
+            let ptr = sasl_malloc(5);
+            assert_eq!(ptr as usize % HEADER, 0);
+            assert_eq!(allocation(ptr as *mut u8).1, 5);
+            bytes(ptr, 5).copy_from_slice(b"hello");
+
+            // Growing and shrinking preserve the data and record the size.
+            let ptr = sasl_realloc(ptr, 100);
+            assert_eq!(allocation(ptr as *mut u8).1, 100);
+            assert_eq!(&bytes(ptr, 5)[..], b"hello");
+            let ptr = sasl_realloc(ptr, 2);
+            assert_eq!(allocation(ptr as *mut u8).1, 2);
+            assert_eq!(&bytes(ptr, 2)[..], b"he");
+            sasl_free(ptr);
+
+            let ptr = sasl_calloc(3, 4);
+            assert_eq!(allocation(ptr as *mut u8).1, 12);
+            assert_eq!(&bytes(ptr, 12)[..], &[0; 12]);
+            sasl_free(ptr);
+            assert!(sasl_calloc(usize::MAX, 2).is_null());
+            assert!(sasl_malloc(usize::MAX).is_null());
+            sasl_free(ptr::null_mut());
+
+            let ptr = sasl_realloc(ptr::null_mut(), 4);
+            assert_eq!(allocation(ptr as *mut u8).1, 4);
+            sasl_free(ptr);
+
+            // When zeroizing, data is always moved to a new allocation, so
+            // that the old one can be wiped.
+            ZEROIZE.store(true, Ordering::SeqCst);
+            let ptr = sasl_malloc(6);
+            bytes(ptr, 6).copy_from_slice(b"secret");
+            let new = sasl_realloc(ptr, 3);
+            assert_ne!(new, ptr);
+            assert_eq!(allocation(new as *mut u8).1, 3);
+            assert_eq!(&bytes(new, 3)[..], b"sec");
+            zeroize(new as *mut u8, 3);
+            assert_eq!(&bytes(new, 3)[..], &[0; 3]);
+            sasl_free(new);
+            ZEROIZE.store(false, Ordering::SeqCst);
+        }
+    }
+}
@@ -0,0 +1,328 @@
+// Copyright Materialize, Inc. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License in the LICENSE file at the
+// root of this repository, or online at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Asynchronous security layers.
+//!
+//! This module is the [Tokio] counterpart to the [`stream`](crate::stream)
+//! module, and is available only when the `tokio` feature is enabled.
+//!
+//! [Tokio]: https://tokio.rs
+
+use std::io::{self, IoSlice};
+use std::pin::Pin;
+use std::task::{ready, Context, Poll};
+
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+
+use crate::connection::Connection;
+use crate::error::SaslError;
+use crate::stream::{Codec, Layer};
+
+/// An asynchronous stream protected by a negotiated security layer.
+///
+/// This is the asynchronous equivalent of
+/// [`SaslStream`](crate::stream::SaslStream), and frames data in the same way.
+/// Partially read frames are retained across polls, so the underlying stream
+/// may deliver data in arbitrarily small pieces.
+#[derive(Debug)]
+pub struct AsyncSaslStream<C, S> {
+    conn: C,
+    inner: S,
+    layer: Option<Layer>,
+}
+
+impl<C, S> AsyncSaslStream<C, S>
+where
+    C: Connection,
+{
+    /// Wraps `inner` in the security layer negotiated by the completed
+    /// exchange on `conn`.
+    pub fn new(conn: C, inner: S) -> Result<AsyncSaslStream<C, S>, SaslError> {
+        let layer = Layer::negotiated(&conn)?;
+        Ok(AsyncSaslStream { conn, inner, layer })
+    }
+
+    /// Reports whether a security layer is in effect, i.e., whether data is
+    /// encoded rather than passed through unchanged.
+    pub fn is_protected(&self) -> bool {
+        self.layer.is_some()
+    }
+
+    /// Returns a reference to the connection.
+    pub fn connection(&self) -> &C {
+        &self.conn
+    }
+
+    /// Returns a reference to the underlying stream.
+    pub fn get_ref(&self) -> &S {
+        &self.inner
+    }
+
+    /// Returns a mutable reference to the underlying stream.
+    ///
+    /// Reading from or writing to the underlying stream directly will corrupt
+    /// the framing of the security layer.
+    pub fn get_mut(&mut self) -> &mut S {
+        &mut self.inner
+    }
+
+    /// Unwraps the stream, returning the connection and the underlying
+    /// stream.
+    ///
+    /// Any buffered data is lost.
+    pub fn into_inner(self) -> (C, S) {
+        (self.conn, self.inner)
+    }
+}
+
+impl<C, S> AsyncRead for AsyncSaslStream<C, S>
+where
+    C: Connection + Unpin,
+    S: AsyncRead + Unpin,
+{
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        match &mut this.layer {
+            None => Pin::new(&mut this.inner).poll_read(cx, buf),
+            Some(layer) => poll_read_and_decode(layer, &mut this.conn, &mut this.inner, cx, buf),
+        }
+    }
+}
+
+impl<C, S> AsyncWrite for AsyncSaslStream<C, S>
+where
+    C: Connection + Unpin,
+    S: AsyncWrite + Unpin,
+{
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+        match &mut this.layer {
+            None => Pin::new(&mut this.inner).poll_write(cx, buf),
+            Some(layer) => poll_encode_and_write(layer, &mut this.conn, &mut this.inner, cx, buf),
+        }
+    }
+
+    fn poll_write_vectored(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        bufs: &[IoSlice<'_>],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+        match &mut this.layer {
+            None => Pin::new(&mut this.inner).poll_write_vectored(cx, bufs),
+            Some(layer) => {
+                poll_encode_and_write_vectored(layer, &mut this.conn, &mut this.inner, cx, bufs)
+            }
+        }
+    }
+
+    fn is_write_vectored(&self) -> bool {
+        self.layer.is_some() || self.inner.is_write_vectored()
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        if let Some(layer) = &mut this.layer {
+            ready!(poll_write_encoded(layer, &mut this.inner, cx))?;
+        }
+        Pin::new(&mut this.inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        if let Some(layer) = &mut this.layer {
+            ready!(poll_write_encoded(layer, &mut this.inner, cx))?;
+        }
+        Pin::new(&mut this.inner).poll_shutdown(cx)
+    }
+}
+
+/// Reads from `inner` until a frame is decoded, and copies as much of the
+/// decoded data as fits into `buf`.
+fn poll_read_and_decode<C, S>(
+    layer: &mut Layer,
+    conn: &mut C,
+    inner: &mut S,
+    cx: &mut Context<'_>,
+    buf: &mut ReadBuf<'_>,
+) -> Poll<io::Result<()>>
+where
+    C: Codec,
+    S: AsyncRead + Unpin,
+{
+    loop {
+        if layer.has_decoded() || buf.remaining() == 0 {
+            let n = layer.read_decoded(buf.initialize_unfilled());
+            buf.advance(n);
+            return Poll::Ready(Ok(()));
+        }
+        let mut frame = ReadBuf::new(layer.unfilled());
+        ready!(Pin::new(&mut *inner).poll_read(cx, &mut frame))?;
+        let n = frame.filled().len();
+        if n == 0 {
+            if layer.at_boundary() {
+                return Poll::Ready(Ok(()));
+            }
+            return Poll::Ready(Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "stream ended within a frame",
+            )));
+        }
+        layer.advance(conn, n)?;
+    }
+}
+
+/// Encodes a prefix of `buf` and writes it to `inner`, returning the number
+/// of bytes consumed.
+fn poll_encode_and_write<C, S>(
+    layer: &mut Layer,
+    conn: &mut C,
+    inner: &mut S,
+    cx: &mut Context<'_>,
+    buf: &[u8],
+) -> Poll<io::Result<usize>>
+where
+    C: Codec,
+    S: AsyncWrite + Unpin,
+{
+    ready!(poll_write_encoded(layer, inner, cx))?;
+    if buf.is_empty() {
+        return Poll::Ready(Ok(0));
+    }
+    let n = layer.encode(conn, buf)?;
+    // `buf` has been consumed, so any error writing out the encoded data is
+    // left to be reported by the next write or flush, which will encounter it
+    // again.
+    let _ = poll_write_encoded(layer, inner, cx);
+    Poll::Ready(Ok(n))
+}
+
+/// Like [`poll_encode_and_write`], but for the concatenation of `bufs`.
+fn poll_encode_and_write_vectored<C, S>(
+    layer: &mut Layer,
+    conn: &mut C,
+    inner: &mut S,
+    cx: &mut Context<'_>,
+    bufs: &[IoSlice<'_>],
+) -> Poll<io::Result<usize>>
+where
+    C: Codec,
+    S: AsyncWrite + Unpin,
+{
+    ready!(poll_write_encoded(layer, inner, cx))?;
+    if bufs.iter().all(|buf| buf.is_empty()) {
+        return Poll::Ready(Ok(0));
+    }
+    let n = layer.encode_vectored(conn, bufs)?;
+    // See `poll_encode_and_write`.
+    let _ = poll_write_encoded(layer, inner, cx);
+    Poll::Ready(Ok(n))
+}
+
+/// Writes all buffered encoded data to `inner`.
+fn poll_write_encoded<S>(
+    layer: &mut Layer,
+    inner: &mut S,
+    cx: &mut Context<'_>,
+) -> Poll<io::Result<()>>
+where
+    S: AsyncWrite + Unpin,
+{
+    while !layer.encoded().is_empty() {
+        match ready!(Pin::new(&mut *inner).poll_write(cx, layer.encoded()))? {
+            0 => {
+                return Poll::Ready(Err(io::Error::new(
+                    io::ErrorKind::WriteZero,
+                    "failed to write encoded data",
+                )))
+            }
+            n => layer.consume_encoded(n),
+        }
+    }
+    Poll::Ready(Ok(()))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::future;
+
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    use super::*;
+    use crate::stream::tests::{frame_lens, Framing};
+
+    #[tokio::test]
+    async fn test_read_reassembles_frames() {
+        let mut wire = Framing.encode(b"hello, ").unwrap();
+        wire.extend(Framing.encode(b"world").unwrap());
+
+        // A tiny pipe delivers the frames, headers included, a few bytes per
+        // poll.
+        let (mut writer, mut reader) = tokio::io::duplex(3);
+        let writer = tokio::spawn(async move {
+            writer.write_all(&wire).await.unwrap();
+        });
+        let mut layer = Layer::new(16, 16);
+        let mut out = vec![];
+        loop {
+            let mut data = [0; 16];
+            let mut buf = ReadBuf::new(&mut data);
+            future::poll_fn(|cx| {
+                poll_read_and_decode(&mut layer, &mut Framing, &mut reader, cx, &mut buf)
+            })
+            .await
+            .unwrap();
+            if buf.filled().is_empty() {
+                break;
+            }
+            out.extend_from_slice(buf.filled());
+        }
+        assert_eq!(out, b"hello, world");
+        writer.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_write_splits_frames() {
+        let (mut writer, mut reader) = tokio::io::duplex(3);
+        let reader = tokio::spawn(async move {
+            let mut wire = vec![];
+            reader.read_to_end(&mut wire).await.unwrap();
+            wire
+        });
+        let mut layer = Layer::new(5, 16);
+        let mut data = &b"hello, world"[..];
+        while !data.is_empty() {
+            let n = future::poll_fn(|cx| {
+                poll_encode_and_write(&mut layer, &mut Framing, &mut writer, cx, data)
+            })
+            .await
+            .unwrap();
+            data = &data[n..];
+        }
+        future::poll_fn(|cx| poll_write_encoded(&mut layer, &mut writer, cx))
+            .await
+            .unwrap();
+        drop(writer);
+        assert_eq!(frame_lens(&reader.await.unwrap()), [5, 5, 2]);
+    }
+}
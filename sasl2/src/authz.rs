@@ -0,0 +1,160 @@
+// Copyright Materialize, Inc. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License in the LICENSE file at the
+// root of this repository, or online at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Proxy authorization policies.
+//!
+//! A client may authenticate as one identity, the authentication identity,
+//! and ask to act as another, the authorization identity. At the end of every
+//! successful exchange, libsasl2 asks the [`AuthorizationPolicy`] installed
+//! with [`Callbacks::authorization_policy`] whether to permit this
+//! (`SASL_CB_PROXY_POLICY`).
+//!
+//! [`RuleTable`] is a policy built from glob patterns:
+//!
+//! ```no_run
+//! use sasl2::authz::RuleTable;
+//! use sasl2::callbacks::Callbacks;
+//! use sasl2::server::SaslServer;
+//!
+//! let rules = RuleTable::new()
+//!     .allow("admin@*", "*")
+//!     .allow("ops-?", "*@tenant-a");
+//! let server = SaslServer::builder("postgres")
+//!     .callbacks(Callbacks::new().authorization_policy(rules))
+//!     .build()?;
+//! # Ok::<(), sasl2::error::SaslError>(())
+//! ```
+//!
+//! [`Callbacks::authorization_policy`]: crate::callbacks::Callbacks::authorization_policy
+
+use crate::auxprop::AuxProps;
+use crate::callbacks::ConnectionInfo;
+
+/// The outcome of an authorization request.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Decision {
+    /// The authentication identity may act as the requested user.
+    Allow,
+    /// The request is denied for the contained reason, which is recorded as
+    /// the connection's error detail.
+    Deny(String),
+}
+
+/// Decides whether an authenticated user may act as another user.
+pub trait AuthorizationPolicy: Send + Sync {
+    /// Decides whether `auth_identity` may act as `requested_user`.
+    ///
+    /// libsasl2 consults the policy even when no separate authorization
+    /// identity was requested, in which case both identities are equal.
+    /// `default_realm` is the realm that users belong to when they do not
+    /// specify one. The policy may populate requested auxiliary properties of
+    /// the authorization identity in `props`.
+    fn authorize(
+        &self,
+        conn: &ConnectionInfo,
+        requested_user: &str,
+        auth_identity: &str,
+        default_realm: Option<&str>,
+        props: &mut AuxProps,
+    ) -> Decision;
+}
+
+/// An [`AuthorizationPolicy`] that permits users to act as themselves, and
+/// otherwise consults a list of rules.
+///
+/// Each rule pairs a pattern for authentication identities with a pattern
+/// for the authorization identities they may request. In patterns, `*`
+/// matches any sequence of characters and `?` matches any single character.
+/// Matching is case-sensitive.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RuleTable {
+    rules: Vec<(String, String)>,
+}
+
+impl RuleTable {
+    /// Creates a rule table that only permits users to act as themselves.
+    pub fn new() -> RuleTable {
+        RuleTable::default()
+    }
+
+    /// Permits authentication identities matching `auth_identity` to act as
+    /// users matching `requested_user`.
+    pub fn allow(mut self, auth_identity: &str, requested_user: &str) -> RuleTable {
+        self.rules
+            .push((auth_identity.into(), requested_user.into()));
+        self
+    }
+
+    /// Reports whether `auth_identity` may act as `requested_user`.
+    pub fn permits(&self, auth_identity: &str, requested_user: &str) -> bool {
+        auth_identity == requested_user
+            || self.rules.iter().any(|(authn, authz)| {
+                glob_match(authn, auth_identity) && glob_match(authz, requested_user)
+            })
+    }
+}
+
+impl AuthorizationPolicy for RuleTable {
+    fn authorize(
+        &self,
+        _conn: &ConnectionInfo,
+        requested_user: &str,
+        auth_identity: &str,
+        _default_realm: Option<&str>,
+        _props: &mut AuxProps,
+    ) -> Decision {
+        if self.permits(auth_identity, requested_user) {
+            Decision::Allow
+        } else {
+            Decision::Deny(format!(
+                "{} may not act as {}",
+                auth_identity, requested_user
+            ))
+        }
+    }
+}
+
+/// Matches `s` against a pattern in which `*` matches any sequence of
+/// characters and `?` matches any single character.
+fn glob_match(pattern: &str, s: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let s: Vec<char> = s.chars().collect();
+    let (mut p, mut i) = (0, 0);
+    // The position of the last `*` in the pattern, and of the character in
+    // `s` that it was last tried against.
+    let mut backtrack = None;
+    while i < s.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                backtrack = Some((p, i));
+                p += 1;
+            }
+            Some(c) if *c == '?' || *c == s[i] => {
+                p += 1;
+                i += 1;
+            }
+            _ => match backtrack {
+                // Let the last `*` absorb one more character.
+                Some((star, start)) => {
+                    backtrack = Some((star, start + 1));
+                    p = star + 1;
+                    i = start + 1;
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|c| *c == '*')
+}
@@ -0,0 +1,137 @@
+// Copyright Materialize, Inc. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License in the LICENSE file at the
+// root of this repository, or online at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Auxiliary properties.
+//!
+//! Auxiliary properties are per-user attributes, like passwords or email
+//! addresses, that libsasl2 looks up during authentication. The application
+//! requests the properties it is interested in with
+//! [`SaslServer::request_aux_properties`], and reads them once the exchange
+//! completes with [`SaslServer::aux_properties`]. Callbacks that consult the
+//! application's own user store, like a
+//! [`PasswordVerifier`](crate::userdb::PasswordVerifier), can populate them.
+//!
+//! By convention, properties whose names start with `*` describe the
+//! authentication identity, and all others the authorization identity.
+//!
+//! [`SaslServer::request_aux_properties`]: crate::server::SaslServer::request_aux_properties
+//! [`SaslServer::aux_properties`]: crate::server::SaslServer::aux_properties
+
+use std::ffi::CStr;
+use std::fmt;
+use std::marker::PhantomData;
+use std::ptr;
+
+use libc::{c_char, c_int};
+use sasl2_sys::prop::{prop_get, prop_set, propctx};
+use sasl2_sys::sasl::SASL_OK;
+
+use crate::error::SaslError;
+use crate::util;
+
+/// The auxiliary properties of a connection.
+pub struct AuxProps<'a> {
+    ctx: *mut propctx,
+    _marker: PhantomData<&'a mut propctx>,
+}
+
+impl<'a> AuxProps<'a> {
+    /// Wraps a property context.
+    ///
+    /// # Safety
+    ///
+    /// `ctx` must be null or a valid property context that outlives `'a`.
+    pub(crate) unsafe fn from_raw(ctx: *mut propctx) -> AuxProps<'a> {
+        AuxProps {
+            ctx,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Returns the names of the requested properties.
+    pub fn names(&self) -> Vec<String> {
+        self.entries()
+            .filter_map(|(name, _)| name.to_str().ok().map(Into::into))
+            .collect()
+    }
+
+    /// Returns the values of the named property, or `None` if the property
+    /// was not requested or has no values.
+    pub fn get(&self, name: &str) -> Option<Vec<String>> {
+        let (_, values) = self
+            .entries()
+            .find(|(n, _)| n.to_bytes() == name.as_bytes())?;
+        if values.is_null() {
+            return None;
+        }
+        let mut out = vec![];
+        unsafe {
+            let mut value = values;
+            while !(*value).is_null() {
+                out.push(CStr::from_ptr(*value).to_string_lossy().into_owned());
+                value = value.add(1);
+            }
+        }
+        Some(out)
+    }
+
+    /// Adds a value to the named property.
+    ///
+    /// Only requested properties can be set.
+    pub fn set(&mut self, name: &str, value: &str) -> Result<(), SaslError> {
+        if self.ctx.is_null() {
+            return Err(SaslError::bad_param("no auxiliary properties"));
+        }
+        let name = util::to_cstring(name)?;
+        let value = util::to_cstring(value)?;
+        let res = unsafe {
+            prop_set(
+                self.ctx,
+                name.as_ptr(),
+                value.as_ptr(),
+                value.as_bytes().len() as c_int,
+            )
+        };
+        match res {
+            SASL_OK => Ok(()),
+            _ => Err(SaslError::from_code(res)),
+        }
+    }
+
+    fn entries(&self) -> impl Iterator<Item = (&CStr, *mut *const c_char)> {
+        let mut val = if self.ctx.is_null() {
+            ptr::null()
+        } else {
+            unsafe { prop_get(self.ctx) }
+        };
+        std::iter::from_fn(move || unsafe {
+            if val.is_null() || (*val).name.is_null() {
+                return None;
+            }
+            let entry = (CStr::from_ptr((*val).name), (*val).values);
+            val = val.add(1);
+            Some(entry)
+        })
+    }
+}
+
+impl fmt::Debug for AuxProps<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        // Values may include passwords, so only the names are shown.
+        f.debug_struct("AuxProps")
+            .field("names", &self.names())
+            .finish()
+    }
+}
@@ -0,0 +1,1257 @@
+// Copyright Materialize, Inc. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License in the LICENSE file at the
+// root of this repository, or online at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Callback registration.
+//!
+//! libsasl2 obtains information from the application, like usernames and
+//! passwords, and delegates policy decisions to the application via
+//! callbacks. [`Callbacks`] assembles a set of Rust closures into the
+//! `SASL_CB_LIST_END`-terminated `sasl_callback_t` array that libsasl2
+//! expects, and owns the closures and any data they hand to libsasl2 for as
+//! long as the connection that uses them.
+//!
+//! Panics in callbacks are caught at the FFI boundary and reported to
+//! libsasl2 as `SASL_FAIL`.
+
+use std::alloc::{self, Layout};
+use std::any::Any;
+use std::collections::HashSet;
+use std::ffi::{CStr, CString};
+use std::fmt;
+use std::marker::PhantomData;
+use std::mem;
+use std::panic::{self, AssertUnwindSafe};
+use std::path::{Path, PathBuf};
+use std::ptr::{self, NonNull};
+use std::slice;
+use std::sync::Mutex;
+
+use libc::{c_char, c_int, c_uint, c_ulong, c_void};
+use sasl2_sys::prop::propctx;
+use sasl2_sys::sasl::{
+    sasl_callback_t, sasl_conn_t, sasl_secret_t, sasl_seterror, SASL_BADPARAM, SASL_BADPROT,
+    SASL_BUFOVER, SASL_CB_AUTHNAME, SASL_CB_CANON_USER, SASL_CB_CNONCE, SASL_CB_GETCONFPATH,
+    SASL_CB_GETOPT, SASL_CB_GETPATH, SASL_CB_GETREALM, SASL_CB_LANGUAGE, SASL_CB_LIST_END,
+    SASL_CB_LOG, SASL_CB_PASS, SASL_CB_PROXY_POLICY, SASL_CB_SERVER_USERDB_CHECKPASS,
+    SASL_CB_SERVER_USERDB_SETPASS, SASL_CB_USER, SASL_CB_VERIFYFILE, SASL_CONTINUE,
+    SASL_DEFUSERREALM, SASL_FAIL, SASL_IPLOCALPORT, SASL_IPREMOTEPORT, SASL_LOG_DEBUG,
+    SASL_LOG_ERR, SASL_LOG_FAIL, SASL_LOG_NOTE, SASL_LOG_PASS, SASL_LOG_TRACE, SASL_LOG_WARN,
+    SASL_MECHNAME, SASL_NOAUTHZ, SASL_NOUSER, SASL_OK, SASL_SERVICE, SASL_VRFY_CONF,
+    SASL_VRFY_PASSWD, SASL_VRFY_PLUGIN,
+};
+use sasl2_sys::saslutil::sasl_utf8verify;
+
+use crate::authz::{AuthorizationPolicy, Decision};
+use crate::auxprop::AuxProps;
+use crate::canonuser::{CanonUserFlags, UserCanonicalizer};
+use crate::config::SaslConfig;
+use crate::userdb::{AuthFailure, PasswordStore, PasswordVerifier, SetPasswordFlags};
+use crate::util;
+
+type Proc = unsafe extern "C" fn() -> c_int;
+
+type SimpleFn = dyn Fn() -> Option<String> + Send + Sync;
+type SecretFn = dyn Fn() -> Option<Vec<u8>> + Send + Sync;
+type RealmFn = dyn Fn(&[&str]) -> Option<String> + Send + Sync;
+type LogFn = dyn Fn(LogLevel, &str, &LogSource) + Send + Sync;
+type GetOptFn = dyn Fn(Option<&str>, &str) -> Option<String> + Send + Sync;
+type ProxyPolicyFn = dyn Fn(&str, &str, Option<&str>) -> Result<(), String> + Send + Sync;
+type PathFn = dyn Fn() -> Vec<PathBuf> + Send + Sync;
+type VerifyFileFn = dyn Fn(&Path, FileType) -> bool + Send + Sync;
+type CanonUserFn = dyn Fn(&str, Option<&str>, c_uint) -> Option<String> + Send + Sync;
+
+/// The severity of a message logged by libsasl2 or one of its plugins.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum LogLevel {
+    /// An unusual error (`SASL_LOG_ERR`).
+    Err,
+    /// An authentication failure (`SASL_LOG_FAIL`).
+    Fail,
+    /// A non-fatal warning (`SASL_LOG_WARN`).
+    Warn,
+    /// A more verbose note (`SASL_LOG_NOTE`).
+    Note,
+    /// A debugging message (`SASL_LOG_DEBUG`).
+    Debug,
+    /// A tracing message (`SASL_LOG_TRACE`).
+    Trace,
+    /// A message that may include passwords (`SASL_LOG_PASS`).
+    Pass,
+}
+
+impl LogLevel {
+    fn from_raw(level: c_int) -> Option<LogLevel> {
+        match level {
+            SASL_LOG_ERR => Some(LogLevel::Err),
+            SASL_LOG_FAIL => Some(LogLevel::Fail),
+            SASL_LOG_WARN => Some(LogLevel::Warn),
+            SASL_LOG_NOTE => Some(LogLevel::Note),
+            SASL_LOG_DEBUG => Some(LogLevel::Debug),
+            SASL_LOG_TRACE => Some(LogLevel::Trace),
+            SASL_LOG_PASS => Some(LogLevel::Pass),
+            _ => None,
+        }
+    }
+}
+
+/// The purpose of a file that libsasl2 is about to open.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum FileType {
+    /// A plugin library (`SASL_VRFY_PLUGIN`).
+    Plugin,
+    /// A configuration file (`SASL_VRFY_CONF`).
+    Config,
+    /// A password file (`SASL_VRFY_PASSWD`).
+    Password,
+    /// Any other file (`SASL_VRFY_OTHER`).
+    Other,
+}
+
+impl FileType {
+    fn from_raw(type_: c_uint) -> FileType {
+        match type_ {
+            SASL_VRFY_PLUGIN => FileType::Plugin,
+            SASL_VRFY_CONF => FileType::Config,
+            SASL_VRFY_PASSWD => FileType::Password,
+            _ => FileType::Other,
+        }
+    }
+}
+
+/// The connection that a logged message pertains to.
+///
+/// The fields are only known for messages logged on a connection whose own
+/// callbacks include the log callback. Messages logged via global callbacks
+/// have no source.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct LogSource {
+    pub(crate) service: Option<String>,
+    pub(crate) mechanism: Option<String>,
+    pub(crate) remote_addr: Option<String>,
+}
+
+impl LogSource {
+    /// Returns the service of the connection, e.g., `"ldap"`.
+    pub fn service(&self) -> Option<&str> {
+        self.service.as_deref()
+    }
+
+    /// Returns the mechanism of the connection, once an exchange has
+    /// started.
+    pub fn mechanism(&self) -> Option<&str> {
+        self.mechanism.as_deref()
+    }
+
+    /// Returns the remote address of the connection in libsasl2's
+    /// `ip;port` format, if one was configured.
+    pub fn remote_addr(&self) -> Option<&str> {
+        self.remote_addr.as_deref()
+    }
+}
+
+/// The connection on whose behalf a callback is invoked.
+pub struct ConnectionInfo<'a> {
+    conn: *mut sasl_conn_t,
+    _marker: PhantomData<&'a sasl_conn_t>,
+}
+
+impl<'a> ConnectionInfo<'a> {
+    /// Wraps a connection.
+    ///
+    /// # Safety
+    ///
+    /// `conn` must be a valid connection that outlives `'a`.
+    pub(crate) unsafe fn from_raw(conn: *mut sasl_conn_t) -> ConnectionInfo<'a> {
+        ConnectionInfo {
+            conn,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Returns the service of the connection, e.g., `"ldap"`.
+    pub fn service(&self) -> Option<String> {
+        self.prop_str(SASL_SERVICE)
+    }
+
+    /// Returns the mechanism of the exchange in progress.
+    pub fn mechanism(&self) -> Option<String> {
+        self.prop_str(SASL_MECHNAME)
+    }
+
+    /// Returns the realm that users belong to when they do not specify one.
+    pub fn user_realm(&self) -> Option<String> {
+        self.prop_str(SASL_DEFUSERREALM)
+    }
+
+    /// Returns the local address of the connection in libsasl2's `ip;port`
+    /// format, if one was configured.
+    pub fn local_addr(&self) -> Option<String> {
+        self.prop_str(SASL_IPLOCALPORT)
+    }
+
+    /// Returns the remote address of the connection in libsasl2's `ip;port`
+    /// format, if one was configured.
+    pub fn remote_addr(&self) -> Option<String> {
+        self.prop_str(SASL_IPREMOTEPORT)
+    }
+
+    fn prop_str(&self, prop: c_uint) -> Option<String> {
+        if self.conn.is_null() {
+            return None;
+        }
+        unsafe { util::getprop_str(self.conn, prop) }
+    }
+}
+
+impl fmt::Debug for ConnectionInfo<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("ConnectionInfo")
+            .field("service", &self.service())
+            .field("remote_addr", &self.remote_addr())
+            .finish()
+    }
+}
+
+struct Callback {
+    id: c_ulong,
+    proc_: Proc,
+    context: Box<dyn Any + Send + Sync>,
+}
+
+/// A set of callbacks to install on a connection or, when initializing the
+/// library, globally.
+///
+/// Each method registers a closure for one callback ID, replacing any closure
+/// previously registered for that ID. All closures must be `Send + Sync`, as
+/// libsasl2 may invoke global callbacks from any thread.
+#[derive(Default)]
+pub struct Callbacks {
+    callbacks: Vec<Callback>,
+}
+
+impl Callbacks {
+    /// Creates an empty set of callbacks.
+    pub fn new() -> Callbacks {
+        Callbacks::default()
+    }
+
+    /// Registers a closure that supplies the authorization identity
+    /// (`SASL_CB_USER`).
+    ///
+    /// Returning `None` indicates that the client does not wish to act as a
+    /// different identity than the one it authenticates as.
+    pub fn user<F>(self, f: F) -> Callbacks
+    where
+        F: Fn() -> Option<String> + Send + Sync + 'static,
+    {
+        self.simple(SASL_CB_USER, Box::new(f))
+    }
+
+    /// Registers a closure that supplies the authentication identity
+    /// (`SASL_CB_AUTHNAME`).
+    pub fn authname<F>(self, f: F) -> Callbacks
+    where
+        F: Fn() -> Option<String> + Send + Sync + 'static,
+    {
+        self.simple(SASL_CB_AUTHNAME, Box::new(f))
+    }
+
+    /// Registers a closure that supplies a comma-separated list of RFC 1766
+    /// language codes (`SASL_CB_LANGUAGE`).
+    pub fn language<F>(self, f: F) -> Callbacks
+    where
+        F: Fn() -> Option<String> + Send + Sync + 'static,
+    {
+        self.simple(SASL_CB_LANGUAGE, Box::new(f))
+    }
+
+    /// Registers a closure that supplies the client nonce (`SASL_CB_CNONCE`).
+    ///
+    /// This is intended for testing only.
+    pub fn cnonce<F>(self, f: F) -> Callbacks
+    where
+        F: Fn() -> Option<String> + Send + Sync + 'static,
+    {
+        self.simple(SASL_CB_CNONCE, Box::new(f))
+    }
+
+    /// Registers a closure that supplies the password (`SASL_CB_PASS`).
+    pub fn pass<F>(self, f: F) -> Callbacks
+    where
+        F: Fn() -> Option<Vec<u8>> + Send + Sync + 'static,
+    {
+        let context = SecretContext {
+            f: Box::new(f),
+            retained: Mutex::new(vec![]),
+        };
+        self.register(
+            SASL_CB_PASS,
+            unsafe { erase(getsecret as GetSecretProc) },
+            context,
+        )
+    }
+
+    /// Registers a closure that selects a realm from the realms offered by
+    /// the server (`SASL_CB_GETREALM`).
+    pub fn get_realm<F>(self, f: F) -> Callbacks
+    where
+        F: Fn(&[&str]) -> Option<String> + Send + Sync + 'static,
+    {
+        let context = RealmContext {
+            f: Box::new(f),
+            retained: Retained::default(),
+        };
+        self.register(
+            SASL_CB_GETREALM,
+            unsafe { erase(getrealm as GetRealmProc) },
+            context,
+        )
+    }
+
+    /// Registers a closure that receives messages logged by libsasl2 and its
+    /// plugins (`SASL_CB_LOG`).
+    pub fn log<F>(self, f: F) -> Callbacks
+    where
+        F: Fn(LogLevel, &str) + Send + Sync + 'static,
+    {
+        self.log_with_source(move |level, message, _| f(level, message))
+    }
+
+    /// Like [`Callbacks::log`], but the closure also receives the connection
+    /// that the message was logged on.
+    ///
+    /// See the [`logging`](crate::logging) module for closures that forward
+    /// messages to the `log` or `tracing` crates.
+    pub fn log_with_source<F>(self, f: F) -> Callbacks
+    where
+        F: Fn(LogLevel, &str, &LogSource) + Send + Sync + 'static,
+    {
+        let context = LogContext {
+            f: Box::new(f),
+            source: Mutex::new(LogSource::default()),
+        };
+        self.register(SASL_CB_LOG, unsafe { erase(log as LogProc) }, context)
+    }
+
+    /// Registers a closure that supplies configuration options
+    /// (`SASL_CB_GETOPT`).
+    ///
+    /// The closure receives the name of the plugin requesting the option, or
+    /// `None` if the option is requested by libsasl2 itself, and the name of
+    /// the option.
+    pub fn get_opt<F>(self, f: F) -> Callbacks
+    where
+        F: Fn(Option<&str>, &str) -> Option<String> + Send + Sync + 'static,
+    {
+        let context = GetOptContext {
+            f: Box::new(f),
+            retained: Retained::default(),
+        };
+        self.register(
+            SASL_CB_GETOPT,
+            unsafe { erase(getopt as GetOptProc) },
+            context,
+        )
+    }
+
+    /// Serves the options in `config` through the `SASL_CB_GETOPT` callback,
+    /// replacing any closure registered with [`Callbacks::get_opt`].
+    pub fn config(self, config: SaslConfig) -> Callbacks {
+        self.get_opt(move |plugin, option| config.get(plugin, option))
+    }
+
+    /// Registers a closure that supplies the directories to search for
+    /// plugins (`SASL_CB_GETPATH`).
+    ///
+    /// Plugins are loaded when the library is initialized, so this callback
+    /// is only consulted as a global callback. To set a fixed path, see
+    /// [`context::set_plugin_path`](crate::context::set_plugin_path).
+    pub fn plugin_path<F>(self, f: F) -> Callbacks
+    where
+        F: Fn() -> Vec<PathBuf> + Send + Sync + 'static,
+    {
+        let context = PathContext {
+            f: Box::new(f),
+            retained: Retained::default(),
+        };
+        self.register(
+            SASL_CB_GETPATH,
+            unsafe { erase(getpath as GetPathProc) },
+            context,
+        )
+    }
+
+    /// Registers a closure that supplies the directories to search for the
+    /// server's configuration file (`SASL_CB_GETCONFPATH`).
+    ///
+    /// The configuration file is read when the server side is initialized,
+    /// so this callback is only consulted as a global callback. To set a
+    /// fixed path, see
+    /// [`context::set_config_path`](crate::context::set_config_path).
+    pub fn config_path<F>(self, f: F) -> Callbacks
+    where
+        F: Fn() -> Vec<PathBuf> + Send + Sync + 'static,
+    {
+        let context = PathContext {
+            f: Box::new(f),
+            retained: Retained::default(),
+        };
+        self.register(
+            SASL_CB_GETCONFPATH,
+            unsafe { erase(getconfpath as GetConfPathProc) },
+            context,
+        )
+    }
+
+    /// Registers a closure that decides whether libsasl2 may open a file
+    /// (`SASL_CB_VERIFYFILE`).
+    ///
+    /// The closure receives the path of the file and its purpose. Returning
+    /// `false` makes libsasl2 skip the file, e.g., to restrict the plugins
+    /// that are loaded to an allowlist. Plugins and configuration files are
+    /// opened when the library is initialized, so these are only verified by
+    /// a global callback.
+    pub fn verify_file<F>(self, f: F) -> Callbacks
+    where
+        F: Fn(&Path, FileType) -> bool + Send + Sync + 'static,
+    {
+        let context = VerifyFileContext { f: Box::new(f) };
+        self.register(
+            SASL_CB_VERIFYFILE,
+            unsafe { erase(verifyfile as VerifyFileProc) },
+            context,
+        )
+    }
+
+    /// Registers a closure that decides whether an authenticated user may act
+    /// as another user (`SASL_CB_PROXY_POLICY`).
+    ///
+    /// The closure receives the requested authorization identity, the
+    /// authenticated identity, and the default realm, if any. Returning an
+    /// error denies the request with the given reason.
+    pub fn proxy_policy<F>(self, f: F) -> Callbacks
+    where
+        F: Fn(&str, &str, Option<&str>) -> Result<(), String> + Send + Sync + 'static,
+    {
+        self.authorization_policy(ProxyPolicyFnPolicy(Box::new(f)))
+    }
+
+    /// Registers a policy that decides whether an authenticated user may act
+    /// as another user (`SASL_CB_PROXY_POLICY`), replacing any closure
+    /// registered with [`Callbacks::proxy_policy`].
+    ///
+    /// See the [`authz`](crate::authz) module for details.
+    pub fn authorization_policy<P>(self, policy: P) -> Callbacks
+    where
+        P: AuthorizationPolicy + 'static,
+    {
+        let context = ProxyPolicyContext {
+            policy: Box::new(policy),
+        };
+        self.register(
+            SASL_CB_PROXY_POLICY,
+            unsafe { erase(proxy_policy as ProxyPolicyProc) },
+            context,
+        )
+    }
+
+    /// Registers a closure that canonicalizes usernames
+    /// (`SASL_CB_CANON_USER`).
+    ///
+    /// The closure receives the username, the user realm, if any, and the
+    /// `SASL_CU_*` flags describing which identities are being
+    /// canonicalized. Returning `None` rejects the username.
+    pub fn canon_user<F>(self, f: F) -> Callbacks
+    where
+        F: Fn(&str, Option<&str>, c_uint) -> Option<String> + Send + Sync + 'static,
+    {
+        self.user_canonicalizer(CanonUserFnCanonicalizer(Box::new(f)))
+    }
+
+    /// Registers a canonicalizer for usernames (`SASL_CB_CANON_USER`),
+    /// replacing any closure registered with [`Callbacks::canon_user`].
+    ///
+    /// See the [`canonuser`](crate::canonuser) module for details.
+    pub fn user_canonicalizer<C>(self, canonicalizer: C) -> Callbacks
+    where
+        C: UserCanonicalizer + 'static,
+    {
+        let context = CanonUserContext {
+            canonicalizer: Box::new(canonicalizer),
+        };
+        self.register(
+            SASL_CB_CANON_USER,
+            unsafe { erase(canon_user as CanonUserProc) },
+            context,
+        )
+    }
+
+    /// Registers a verifier for plaintext passwords
+    /// (`SASL_CB_SERVER_USERDB_CHECKPASS`).
+    ///
+    /// See the [`userdb`](crate::userdb) module for details.
+    pub fn password_verifier<V>(self, verifier: V) -> Callbacks
+    where
+        V: PasswordVerifier + 'static,
+    {
+        let context = CheckPassContext {
+            verifier: Box::new(verifier),
+            failure: Mutex::new(None),
+        };
+        self.register(
+            SASL_CB_SERVER_USERDB_CHECKPASS,
+            unsafe { erase(checkpass as CheckPassProc) },
+            context,
+        )
+    }
+
+    /// Registers a store for passwords set with
+    /// [`SaslServer::set_password`](crate::server::SaslServer::set_password)
+    /// (`SASL_CB_SERVER_USERDB_SETPASS`).
+    ///
+    /// See the [`userdb`](crate::userdb) module for details.
+    pub fn password_store<S>(self, store: S) -> Callbacks
+    where
+        S: PasswordStore + 'static,
+    {
+        let context = SetPassContext {
+            store: Box::new(store),
+        };
+        self.register(
+            SASL_CB_SERVER_USERDB_SETPASS,
+            unsafe { erase(setpass as SetPassProc) },
+            context,
+        )
+    }
+
+    /// Reports whether a callback is registered for `id`.
+    pub fn contains(&self, id: c_ulong) -> bool {
+        self.callbacks.iter().any(|cb| cb.id == id)
+    }
+
+    /// Updates the source reported with messages passed to the log callback,
+    /// if one is registered.
+    pub(crate) fn update_log_source<F>(&self, f: F)
+    where
+        F: FnOnce(&mut LogSource),
+    {
+        let context = self
+            .callbacks
+            .iter()
+            .filter(|cb| cb.id == SASL_CB_LOG)
+            .find_map(|cb| cb.context.downcast_ref::<LogContext>());
+        if let Some(context) = context {
+            f(&mut context.source.lock().unwrap_or_else(|e| e.into_inner()));
+        }
+    }
+
+    /// Takes the reason the password verifier, if one is registered, gave
+    /// for rejecting the most recently checked password.
+    pub(crate) fn take_auth_failure(&self) -> Option<AuthFailure> {
+        let context = self
+            .callbacks
+            .iter()
+            .filter(|cb| cb.id == SASL_CB_SERVER_USERDB_CHECKPASS)
+            .find_map(|cb| cb.context.downcast_ref::<CheckPassContext>())?;
+        context
+            .failure
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .take()
+    }
+
+    fn simple(self, id: c_ulong, f: Box<SimpleFn>) -> Callbacks {
+        let context = SimpleContext {
+            f,
+            retained: Retained::default(),
+        };
+        self.register(id, unsafe { erase(getsimple as GetSimpleProc) }, context)
+    }
+
+    fn register<C>(mut self, id: c_ulong, proc_: Proc, context: C) -> Callbacks
+    where
+        C: Any + Send + Sync,
+    {
+        self.callbacks.retain(|cb| cb.id != id);
+        self.callbacks.push(Callback {
+            id,
+            proc_,
+            context: Box::new(context),
+        });
+        self
+    }
+
+    /// Builds the `sasl_callback_t` array for these callbacks.
+    ///
+    /// Each ID in `interactive` for which no callback is registered is
+    /// declared with no procedure, which instructs libsasl2 to request the
+    /// information via `SASL_INTERACT` instead.
+    ///
+    /// The returned array refers to data owned by `self`, and must not be
+    /// used after `self` is dropped.
+    pub(crate) fn to_raw(&self, interactive: &[c_ulong]) -> Box<[sasl_callback_t]> {
+        let registered = self.callbacks.iter().map(|cb| sasl_callback_t {
+            id: cb.id,
+            proc_: Some(cb.proc_),
+            context: &*cb.context as *const (dyn Any + Send + Sync) as *mut c_void,
+        });
+        let interactive = interactive
+            .iter()
+            .filter(|id| !self.contains(**id))
+            .map(|id| sasl_callback_t {
+                id: *id,
+                proc_: None,
+                context: ptr::null_mut(),
+            });
+        let end = sasl_callback_t {
+            id: SASL_CB_LIST_END,
+            proc_: None,
+            context: ptr::null_mut(),
+        };
+        registered.chain(interactive).chain(Some(end)).collect()
+    }
+}
+
+impl fmt::Debug for Callbacks {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_list()
+            .entries(self.callbacks.iter().map(|cb| cb.id))
+            .finish()
+    }
+}
+
+/// Erases the type of a callback procedure, as `sasl_callback_t` requires.
+///
+/// # Safety
+///
+/// `F` must be an `unsafe extern "C" fn` pointer type.
+unsafe fn erase<F: Copy>(f: F) -> Proc {
+    assert_eq!(mem::size_of::<F>(), mem::size_of::<Proc>());
+    mem::transmute_copy(&f)
+}
+
+/// Runs a callback body, converting a panic into `SASL_FAIL` rather than
+/// unwinding into C.
+fn guard<F>(f: F) -> c_int
+where
+    F: FnOnce() -> c_int,
+{
+    panic::catch_unwind(AssertUnwindSafe(f)).unwrap_or(SASL_FAIL)
+}
+
+/// Converts a possibly-null C string into a `&str`.
+unsafe fn opt_str<'a>(s: *const c_char) -> Option<&'a str> {
+    if s.is_null() {
+        None
+    } else {
+        CStr::from_ptr(s).to_str().ok()
+    }
+}
+
+/// Converts a pointer and length into a `&str`.
+unsafe fn len_str<'a>(s: *const c_char, len: c_uint) -> Option<&'a str> {
+    if s.is_null() {
+        Some("")
+    } else {
+        std::str::from_utf8(slice::from_raw_parts(s as *const u8, len as usize)).ok()
+    }
+}
+
+/// Keeps strings handed to libsasl2 alive for as long as the callback that
+/// produced them.
+///
+/// Identical strings share one allocation, so a callback that repeatedly
+/// returns the same value does not accumulate memory.
+#[derive(Default)]
+struct Retained(Mutex<HashSet<CString>>);
+
+impl Retained {
+    fn retain(&self, s: CString) -> *const c_char {
+        let mut retained = self.0.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(existing) = retained.get(&s) {
+            return existing.as_ptr();
+        }
+        // Moving the `CString` into the set does not move its heap buffer.
+        let ptr = s.as_ptr();
+        retained.insert(s);
+        ptr
+    }
+}
+
+/// A heap-allocated `sasl_secret_t` that is zeroed when dropped.
+struct Secret {
+    ptr: NonNull<sasl_secret_t>,
+    layout: Layout,
+}
+
+// SAFETY: `Secret` uniquely owns its allocation.
+unsafe impl Send for Secret {}
+unsafe impl Sync for Secret {}
+
+impl Secret {
+    fn new(data: &[u8]) -> Secret {
+        // `sasl_secret_t` is a length followed by a variable-length,
+        // nul-terminated array of bytes.
+        let offset = mem::size_of::<c_ulong>();
+        let size = (offset + data.len() + 1).max(mem::size_of::<sasl_secret_t>());
+        let layout = Layout::from_size_align(size, mem::align_of::<sasl_secret_t>()).unwrap();
+        unsafe {
+            let ptr = alloc::alloc_zeroed(layout) as *mut sasl_secret_t;
+            let ptr = NonNull::new(ptr).unwrap_or_else(|| alloc::handle_alloc_error(layout));
+            (*ptr.as_ptr()).len = data.len() as c_ulong;
+            let dst = (ptr.as_ptr() as *mut u8).add(offset);
+            ptr::copy_nonoverlapping(data.as_ptr(), dst, data.len());
+            Secret { ptr, layout }
+        }
+    }
+
+    fn data(&self) -> &[u8] {
+        unsafe {
+            let len = (*self.ptr.as_ptr()).len as usize;
+            let data = (self.ptr.as_ptr() as *const u8).add(mem::size_of::<c_ulong>());
+            slice::from_raw_parts(data, len)
+        }
+    }
+}
+
+impl Drop for Secret {
+    fn drop(&mut self) {
+        unsafe {
+            let base = self.ptr.as_ptr() as *mut u8;
+            for i in 0..self.layout.size() {
+                ptr::write_volatile(base.add(i), 0);
+            }
+            alloc::dealloc(base, self.layout);
+        }
+    }
+}
+
+struct SimpleContext {
+    f: Box<SimpleFn>,
+    retained: Retained,
+}
+
+type GetSimpleProc =
+    unsafe extern "C" fn(*mut c_void, c_int, *mut *const c_char, *mut c_uint) -> c_int;
+
+unsafe extern "C" fn getsimple(
+    context: *mut c_void,
+    _id: c_int,
+    result: *mut *const c_char,
+    len: *mut c_uint,
+) -> c_int {
+    guard(|| {
+        let context = &*(context as *const SimpleContext);
+        if result.is_null() {
+            return SASL_BADPARAM;
+        }
+        *result = ptr::null();
+        if let Some(value) = (context.f)() {
+            let value = match CString::new(value) {
+                Ok(value) => value,
+                Err(_) => return SASL_BADPARAM,
+            };
+            if !len.is_null() {
+                *len = value.as_bytes().len() as c_uint;
+            }
+            *result = context.retained.retain(value);
+        }
+        SASL_OK
+    })
+}
+
+struct SecretContext {
+    f: Box<SecretFn>,
+    retained: Mutex<Vec<Secret>>,
+}
+
+type GetSecretProc =
+    unsafe extern "C" fn(*mut sasl_conn_t, *mut c_void, c_int, *mut *mut sasl_secret_t) -> c_int;
+
+unsafe extern "C" fn getsecret(
+    _conn: *mut sasl_conn_t,
+    context: *mut c_void,
+    _id: c_int,
+    psecret: *mut *mut sasl_secret_t,
+) -> c_int {
+    guard(|| {
+        let context = &*(context as *const SecretContext);
+        if psecret.is_null() {
+            return SASL_BADPARAM;
+        }
+        let data = match (context.f)() {
+            Some(data) => data,
+            None => return SASL_FAIL,
+        };
+        let mut retained = context.retained.lock().unwrap_or_else(|e| e.into_inner());
+        let secret = match retained.iter().find(|s| s.data() == &data[..]) {
+            Some(secret) => secret.ptr,
+            None => {
+                let secret = Secret::new(&data);
+                let ptr = secret.ptr;
+                retained.push(secret);
+                ptr
+            }
+        };
+        *psecret = secret.as_ptr();
+        SASL_OK
+    })
+}
+
+struct RealmContext {
+    f: Box<RealmFn>,
+    retained: Retained,
+}
+
+type GetRealmProc =
+    unsafe extern "C" fn(*mut c_void, c_int, *mut *const c_char, *mut *const c_char) -> c_int;
+
+unsafe extern "C" fn getrealm(
+    context: *mut c_void,
+    _id: c_int,
+    availrealms: *mut *const c_char,
+    result: *mut *const c_char,
+) -> c_int {
+    guard(|| {
+        let context = &*(context as *const RealmContext);
+        if result.is_null() {
+            return SASL_BADPARAM;
+        }
+        let mut realms = vec![];
+        if !availrealms.is_null() {
+            let mut realm = availrealms;
+            while !(*realm).is_null() {
+                if let Some(r) = opt_str(*realm) {
+                    realms.push(r);
+                }
+                realm = realm.add(1);
+            }
+        }
+        *result = match (context.f)(&realms) {
+            None => ptr::null(),
+            Some(realm) => match CString::new(realm) {
+                Ok(realm) => context.retained.retain(realm),
+                Err(_) => return SASL_BADPARAM,
+            },
+        };
+        SASL_OK
+    })
+}
+
+struct LogContext {
+    f: Box<LogFn>,
+    source: Mutex<LogSource>,
+}
+
+type LogProc = unsafe extern "C" fn(*mut c_void, c_int, *const c_char) -> c_int;
+
+unsafe extern "C" fn log(context: *mut c_void, level: c_int, message: *const c_char) -> c_int {
+    guard(|| {
+        let context = &*(context as *const LogContext);
+        if let Some(level) = LogLevel::from_raw(level) {
+            if !message.is_null() {
+                // Release the lock before calling out, so that the closure
+                // cannot deadlock against an update.
+                let source = context
+                    .source
+                    .lock()
+                    .unwrap_or_else(|e| e.into_inner())
+                    .clone();
+                (context.f)(level, &CStr::from_ptr(message).to_string_lossy(), &source);
+            }
+        }
+        SASL_OK
+    })
+}
+
+struct PathContext {
+    f: Box<PathFn>,
+    retained: Retained,
+}
+
+impl PathContext {
+    fn path(&self) -> Result<*const c_char, c_int> {
+        let path = util::join_paths((self.f)()).map_err(|_| SASL_BADPARAM)?;
+        Ok(self.retained.retain(path))
+    }
+}
+
+type GetPathProc = unsafe extern "C" fn(*mut c_void, *mut *const c_char) -> c_int;
+
+unsafe extern "C" fn getpath(context: *mut c_void, path: *mut *const c_char) -> c_int {
+    guard(|| {
+        let context = &*(context as *const PathContext);
+        if path.is_null() {
+            return SASL_BADPARAM;
+        }
+        match context.path() {
+            Ok(p) => {
+                *path = p;
+                SASL_OK
+            }
+            Err(res) => res,
+        }
+    })
+}
+
+type GetConfPathProc = unsafe extern "C" fn(*mut c_void, *mut *mut c_char) -> c_int;
+
+unsafe extern "C" fn getconfpath(context: *mut c_void, path: *mut *mut c_char) -> c_int {
+    guard(|| {
+        let context = &*(context as *const PathContext);
+        if path.is_null() {
+            return SASL_BADPARAM;
+        }
+        match context.path() {
+            // libsasl2 does not modify the path.
+            Ok(p) => {
+                *path = p as *mut c_char;
+                SASL_OK
+            }
+            Err(res) => res,
+        }
+    })
+}
+
+struct VerifyFileContext {
+    f: Box<VerifyFileFn>,
+}
+
+type VerifyFileProc = unsafe extern "C" fn(*mut c_void, *const c_char, c_uint) -> c_int;
+
+unsafe extern "C" fn verifyfile(context: *mut c_void, file: *const c_char, type_: c_uint) -> c_int {
+    guard(|| {
+        let context = &*(context as *const VerifyFileContext);
+        if file.is_null() {
+            return SASL_BADPARAM;
+        }
+        // `SASL_CONTINUE` instructs libsasl2 to skip the file.
+        if (context.f)(&util::to_path(file), FileType::from_raw(type_)) {
+            SASL_OK
+        } else {
+            SASL_CONTINUE
+        }
+    })
+}
+
+struct GetOptContext {
+    f: Box<GetOptFn>,
+    retained: Retained,
+}
+
+type GetOptProc = unsafe extern "C" fn(
+    *mut c_void,
+    *const c_char,
+    *const c_char,
+    *mut *const c_char,
+    *mut c_uint,
+) -> c_int;
+
+unsafe extern "C" fn getopt(
+    context: *mut c_void,
+    plugin_name: *const c_char,
+    option: *const c_char,
+    result: *mut *const c_char,
+    len: *mut c_uint,
+) -> c_int {
+    guard(|| {
+        let context = &*(context as *const GetOptContext);
+        let option = match opt_str(option) {
+            Some(option) => option,
+            None => return SASL_BADPARAM,
+        };
+        if result.is_null() {
+            return SASL_BADPARAM;
+        }
+        match (context.f)(opt_str(plugin_name), option) {
+            None => SASL_FAIL,
+            Some(value) => {
+                let value = match CString::new(value) {
+                    Ok(value) => value,
+                    Err(_) => return SASL_BADPARAM,
+                };
+                if !len.is_null() {
+                    *len = value.as_bytes().len() as c_uint;
+                }
+                *result = context.retained.retain(value);
+                SASL_OK
+            }
+        }
+    })
+}
+
+struct ProxyPolicyContext {
+    policy: Box<dyn AuthorizationPolicy>,
+}
+
+/// Adapts a closure registered with [`Callbacks::proxy_policy`].
+struct ProxyPolicyFnPolicy(Box<ProxyPolicyFn>);
+
+impl AuthorizationPolicy for ProxyPolicyFnPolicy {
+    fn authorize(
+        &self,
+        _conn: &ConnectionInfo,
+        requested_user: &str,
+        auth_identity: &str,
+        default_realm: Option<&str>,
+        _props: &mut AuxProps,
+    ) -> Decision {
+        match (self.0)(requested_user, auth_identity, default_realm) {
+            Ok(()) => Decision::Allow,
+            Err(reason) => Decision::Deny(reason),
+        }
+    }
+}
+
+type ProxyPolicyProc = unsafe extern "C" fn(
+    *mut sasl_conn_t,
+    *mut c_void,
+    *const c_char,
+    c_uint,
+    *const c_char,
+    c_uint,
+    *const c_char,
+    c_uint,
+    *mut propctx,
+) -> c_int;
+
+#[allow(clippy::too_many_arguments)]
+unsafe extern "C" fn proxy_policy(
+    conn: *mut sasl_conn_t,
+    context: *mut c_void,
+    requested_user: *const c_char,
+    rlen: c_uint,
+    auth_identity: *const c_char,
+    alen: c_uint,
+    def_realm: *const c_char,
+    urlen: c_uint,
+    propctx: *mut propctx,
+) -> c_int {
+    guard(|| {
+        let context = &*(context as *const ProxyPolicyContext);
+        let (requested_user, auth_identity) =
+            match (len_str(requested_user, rlen), len_str(auth_identity, alen)) {
+                (Some(r), Some(a)) => (r, a),
+                _ => return SASL_BADPARAM,
+            };
+        let def_realm = if def_realm.is_null() {
+            None
+        } else {
+            len_str(def_realm, urlen)
+        };
+        let info = ConnectionInfo::from_raw(conn);
+        let mut props = AuxProps::from_raw(propctx);
+        let decision =
+            context
+                .policy
+                .authorize(&info, requested_user, auth_identity, def_realm, &mut props);
+        match decision {
+            Decision::Allow => SASL_OK,
+            Decision::Deny(reason) => {
+                let reason =
+                    CString::new(reason.replace('\0', "")).expect("interior nul bytes removed");
+                sasl_seterror(conn, 0, b"%s\0".as_ptr() as *const c_char, reason.as_ptr());
+                SASL_NOAUTHZ
+            }
+        }
+    })
+}
+
+struct CanonUserContext {
+    canonicalizer: Box<dyn UserCanonicalizer>,
+}
+
+/// Adapts a closure registered with [`Callbacks::canon_user`].
+struct CanonUserFnCanonicalizer(Box<CanonUserFn>);
+
+impl UserCanonicalizer for CanonUserFnCanonicalizer {
+    fn canonicalize(
+        &self,
+        _conn: &ConnectionInfo,
+        user: &str,
+        realm: Option<&str>,
+        flags: CanonUserFlags,
+    ) -> Option<String> {
+        (self.0)(user, realm, flags.bits())
+    }
+}
+
+type CanonUserProc = unsafe extern "C" fn(
+    *mut sasl_conn_t,
+    *mut c_void,
+    *const c_char,
+    c_uint,
+    c_uint,
+    *const c_char,
+    *mut c_char,
+    c_uint,
+    *mut c_uint,
+) -> c_int;
+
+#[allow(clippy::too_many_arguments)]
+unsafe extern "C" fn canon_user(
+    conn: *mut sasl_conn_t,
+    context: *mut c_void,
+    in_: *const c_char,
+    inlen: c_uint,
+    flags: c_uint,
+    user_realm: *const c_char,
+    out: *mut c_char,
+    out_max: c_uint,
+    out_len: *mut c_uint,
+) -> c_int {
+    guard(|| {
+        let context = &*(context as *const CanonUserContext);
+        if out.is_null() || out_len.is_null() {
+            return SASL_BADPARAM;
+        }
+        if !in_.is_null() && sasl_utf8verify(in_, inlen) != SASL_OK {
+            sasl_seterror(
+                conn,
+                0,
+                b"username is not valid UTF-8\0".as_ptr() as *const c_char,
+            );
+            return SASL_BADPROT;
+        }
+        // Copy the input before writing any output, as libsasl2 may pass
+        // overlapping buffers.
+        let user = match len_str(in_, inlen) {
+            Some(user) => user.to_owned(),
+            None => return SASL_BADPROT,
+        };
+        let info = ConnectionInfo::from_raw(conn);
+        let flags = CanonUserFlags::from_bits_truncate(flags);
+        let canonical =
+            match context
+                .canonicalizer
+                .canonicalize(&info, &user, opt_str(user_realm), flags)
+            {
+                Some(canonical) => canonical,
+                None => return SASL_NOUSER,
+            };
+        if canonical.contains('\0') {
+            return SASL_BADPARAM;
+        }
+        let len = canonical.len();
+        if len > out_max as usize {
+            sasl_seterror(
+                conn,
+                0,
+                // libsasl2's formatter does not support `%u`.
+                b"canonical username exceeds %d bytes\0".as_ptr() as *const c_char,
+                out_max as c_int,
+            );
+            return SASL_BUFOVER;
+        }
+        ptr::copy_nonoverlapping(canonical.as_ptr(), out as *mut u8, len);
+        if len < out_max as usize {
+            *out.add(len) = 0;
+        }
+        *out_len = len as c_uint;
+        SASL_OK
+    })
+}
+
+struct CheckPassContext {
+    verifier: Box<dyn PasswordVerifier>,
+    // When the verifier rejects a password, libsasl2 falls back to other
+    // methods and reports their result instead, so the verifier's reason is
+    // recorded here for the connection to report.
+    failure: Mutex<Option<AuthFailure>>,
+}
+
+type CheckPassProc = unsafe extern "C" fn(
+    *mut sasl_conn_t,
+    *mut c_void,
+    *const c_char,
+    *const c_char,
+    c_uint,
+    *mut propctx,
+) -> c_int;
+
+unsafe extern "C" fn checkpass(
+    conn: *mut sasl_conn_t,
+    context: *mut c_void,
+    user: *const c_char,
+    pass: *const c_char,
+    passlen: c_uint,
+    propctx: *mut propctx,
+) -> c_int {
+    guard(|| {
+        let context = &*(context as *const CheckPassContext);
+        let user = match opt_str(user) {
+            Some(user) => user,
+            None => return SASL_BADPARAM,
+        };
+        if pass.is_null() {
+            return SASL_BADPARAM;
+        }
+        let pass = slice::from_raw_parts(pass as *const u8, passlen as usize);
+        let conn = ConnectionInfo::from_raw(conn);
+        let mut props = AuxProps::from_raw(propctx);
+        let res = context.verifier.check(&conn, user, pass, &mut props);
+        *context.failure.lock().unwrap_or_else(|e| e.into_inner()) = res.err();
+        match res {
+            Ok(()) => SASL_OK,
+            Err(failure) => failure.code(),
+        }
+    })
+}
+
+struct SetPassContext {
+    store: Box<dyn PasswordStore>,
+}
+
+type SetPassProc = unsafe extern "C" fn(
+    *mut sasl_conn_t,
+    *mut c_void,
+    *const c_char,
+    *const c_char,
+    c_uint,
+    *mut propctx,
+    c_uint,
+) -> c_int;
+
+unsafe extern "C" fn setpass(
+    conn: *mut sasl_conn_t,
+    context: *mut c_void,
+    user: *const c_char,
+    pass: *const c_char,
+    passlen: c_uint,
+    propctx: *mut propctx,
+    flags: c_uint,
+) -> c_int {
+    guard(|| {
+        let context = &*(context as *const SetPassContext);
+        let user = match opt_str(user) {
+            Some(user) => user,
+            None => return SASL_BADPARAM,
+        };
+        let flags = SetPasswordFlags::from_bits_truncate(flags);
+        // libsasl2 only sometimes clears the password when disabling the
+        // user.
+        let pass = if pass.is_null() || flags.contains(SetPasswordFlags::DISABLE) {
+            None
+        } else {
+            Some(slice::from_raw_parts(pass as *const u8, passlen as usize))
+        };
+        let conn = ConnectionInfo::from_raw(conn);
+        let mut props = AuxProps::from_raw(propctx);
+        match context.store.set(&conn, user, pass, &mut props, flags) {
+            Ok(()) => SASL_OK,
+            Err(failure) => failure.code(),
+        }
+    })
+}
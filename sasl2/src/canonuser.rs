@@ -0,0 +1,90 @@
+// Copyright Materialize, Inc. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License in the LICENSE file at the
+// root of this repository, or online at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Username canonicalization.
+//!
+//! Before libsasl2 looks up or compares identities, it canonicalizes them,
+//! by default only trimming surrounding whitespace. A [`UserCanonicalizer`]
+//! installed with [`Callbacks::user_canonicalizer`] runs first
+//! (`SASL_CB_CANON_USER`), on clients and servers alike, e.g., to strip the
+//! realm from Kerberos principals:
+//!
+//! ```no_run
+//! use sasl2::callbacks::{Callbacks, ConnectionInfo};
+//! use sasl2::canonuser::{CanonUserFlags, UserCanonicalizer};
+//!
+//! struct StripRealm;
+//!
+//! impl UserCanonicalizer for StripRealm {
+//!     fn canonicalize(
+//!         &self,
+//!         _conn: &ConnectionInfo,
+//!         user: &str,
+//!         _realm: Option<&str>,
+//!         _flags: CanonUserFlags,
+//!     ) -> Option<String> {
+//!         Some(user.split('@').next().unwrap_or(user).into())
+//!     }
+//! }
+//!
+//! let callbacks = Callbacks::new().user_canonicalizer(StripRealm);
+//! ```
+//!
+//! The crate validates that usernames are UTF-8, with `sasl_utf8verify`,
+//! before they are canonicalized, and that the canonical username fits into
+//! libsasl2's buffer afterwards.
+//!
+//! [`Callbacks::user_canonicalizer`]: crate::callbacks::Callbacks::user_canonicalizer
+
+use bitflags::bitflags;
+use libc::c_uint;
+use sasl2_sys::sasl::{
+    SASL_CU_AUTHID, SASL_CU_AUTHZID, SASL_CU_EXTERNALLY_VERIFIED, SASL_CU_OVERRIDE,
+};
+
+use crate::callbacks::ConnectionInfo;
+
+bitflags! {
+    /// The identities being canonicalized.
+    #[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+    pub struct CanonUserFlags: c_uint {
+        /// The authentication identity (`SASL_CU_AUTHID`).
+        const AUTHID = SASL_CU_AUTHID;
+        /// The authorization identity (`SASL_CU_AUTHZID`).
+        const AUTHZID = SASL_CU_AUTHZID;
+        /// The authentication identity was verified outside of libsasl2,
+        /// e.g., by TLS client certificates (`SASL_CU_EXTERNALLY_VERIFIED`).
+        const EXTERNALLY_VERIFIED = SASL_CU_EXTERNALLY_VERIFIED;
+        /// Auxiliary property lookups for the user override existing values
+        /// (`SASL_CU_OVERRIDE`).
+        const OVERRIDE = SASL_CU_OVERRIDE;
+    }
+}
+
+/// Canonicalizes usernames.
+pub trait UserCanonicalizer: Send + Sync {
+    /// Returns the canonical form of `user`, or `None` to reject the user.
+    ///
+    /// `realm` is the realm that users belong to when they do not specify
+    /// one, which is only known on servers. When both identities are being
+    /// canonicalized at once, `flags` contains both `AUTHID` and `AUTHZID`.
+    fn canonicalize(
+        &self,
+        conn: &ConnectionInfo,
+        user: &str,
+        realm: Option<&str>,
+        flags: CanonUserFlags,
+    ) -> Option<String>;
+}
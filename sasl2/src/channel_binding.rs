@@ -0,0 +1,285 @@
+// Copyright Materialize, Inc. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License in the LICENSE file at the
+// root of this repository, or online at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Channel bindings.
+//!
+//! A channel binding ties an exchange to the underlying secure channel, like
+//! a TLS connection, so that an attacker cannot relay the exchange over a
+//! different channel. Mechanisms that support channel binding, like
+//! SCRAM-SHA-256-PLUS, include the binding data in the exchange, and both
+//! peers must supply the same data.
+//!
+//! Channel bindings are applied with [`ClientBuilder::channel_binding`] and
+//! [`ServerBuilder::channel_binding`], or at any time before the exchange
+//! starts via the [`property::ChannelBinding`] property. The data comes from
+//! the TLS library; this module does not depend on any particular one.
+//!
+//! ```
+//! use sasl2::channel_binding::ChannelBinding;
+//!
+//! # let cert_der = include_bytes!("../tests/data/server.der");
+//! let binding = ChannelBinding::tls_server_end_point(cert_der)?.critical(true);
+//! assert_eq!(binding.name(), "tls-server-end-point");
+//! # Ok::<(), sasl2::error::SaslError>(())
+//! ```
+//!
+//! [`ClientBuilder::channel_binding`]: crate::client::ClientBuilder::channel_binding
+//! [`ServerBuilder::channel_binding`]: crate::server::ServerBuilder::channel_binding
+//! [`property::ChannelBinding`]: crate::property::ChannelBinding
+
+use std::convert::TryFrom;
+use std::ffi::CString;
+
+use libc::{c_int, c_ulong};
+use sasl2_sys::sasl::sasl_channel_binding_t;
+use sha2::{Digest, Sha224, Sha256, Sha384, Sha512};
+
+use crate::error::SaslError;
+use crate::util;
+
+/// Channel binding data.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChannelBinding {
+    name: String,
+    critical: bool,
+    data: Vec<u8>,
+}
+
+impl ChannelBinding {
+    /// Constructs a channel binding of the named type (e.g.,
+    /// `"tls-exporter"`) with the given data.
+    ///
+    /// The binding is not critical.
+    pub fn new<D>(name: &str, data: D) -> ChannelBinding
+    where
+        D: Into<Vec<u8>>,
+    {
+        ChannelBinding {
+            name: name.into(),
+            critical: false,
+            data: data.into(),
+        }
+    }
+
+    /// Constructs a `tls-unique` channel binding from the first Finished
+    /// message of the most recent TLS handshake.
+    ///
+    /// `tls-unique` is not defined for TLS 1.3, which should use
+    /// [`ChannelBinding::tls_exporter`] instead.
+    pub fn tls_unique<D>(finished: D) -> ChannelBinding
+    where
+        D: Into<Vec<u8>>,
+    {
+        ChannelBinding::new("tls-unique", finished)
+    }
+
+    /// Constructs a `tls-exporter` channel binding from the output of the TLS
+    /// exporter with the label `EXPORTER-Channel-Binding`, an empty context,
+    /// and a length of 32 bytes, per RFC 9266.
+    pub fn tls_exporter<D>(exported: D) -> ChannelBinding
+    where
+        D: Into<Vec<u8>>,
+    {
+        ChannelBinding::new("tls-exporter", exported)
+    }
+
+    /// Constructs a `tls-server-end-point` channel binding from the
+    /// DER-encoded certificate of the TLS server.
+    ///
+    /// Per RFC 5929, the data is the hash of the certificate, computed with
+    /// the hash function of the certificate's signature algorithm, or with
+    /// SHA-256 if that function is MD5 or SHA-1. Returns a
+    /// [`SaslError::BadParam`] error if the certificate cannot be parsed or
+    /// its signature algorithm does not determine a hash function, as with
+    /// Ed25519.
+    pub fn tls_server_end_point(cert_der: &[u8]) -> Result<ChannelBinding, SaslError> {
+        let hash = cert_hash(cert_der)?;
+        let data = match hash {
+            Hash::Sha224 => Sha224::digest(cert_der).to_vec(),
+            Hash::Sha256 => Sha256::digest(cert_der).to_vec(),
+            Hash::Sha384 => Sha384::digest(cert_der).to_vec(),
+            Hash::Sha512 => Sha512::digest(cert_der).to_vec(),
+        };
+        Ok(ChannelBinding::new("tls-server-end-point", data))
+    }
+
+    /// Declares whether the binding is critical, replacing any previous
+    /// declaration.
+    ///
+    /// A client with a critical binding will only negotiate mechanisms that
+    /// support channel binding. A server with a critical binding rejects
+    /// clients that do not use it, with a [`SaslError::BadBinding`] error.
+    pub fn critical(mut self, critical: bool) -> ChannelBinding {
+        self.critical = critical;
+        self
+    }
+
+    /// Returns the type of the binding, e.g., `"tls-server-end-point"`.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Reports whether the binding is critical.
+    pub fn is_critical(&self) -> bool {
+        self.critical
+    }
+
+    /// Returns the binding data.
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+
+    /// Converts the binding into a `sasl_channel_binding_t`.
+    pub(crate) fn to_raw(&self) -> Result<Box<RawChannelBinding>, SaslError> {
+        let name = util::to_cstring(&self.name)?;
+        let data = self.data.clone().into_boxed_slice();
+        let len = c_ulong::try_from(data.len())
+            .map_err(|_| SaslError::bad_param("channel binding data too long"))?;
+        Ok(Box::new(RawChannelBinding {
+            binding: sasl_channel_binding_t {
+                name: name.as_ptr(),
+                critical: c_int::from(self.critical),
+                len,
+                data: data.as_ptr(),
+            },
+            _name: name,
+            _data: data,
+        }))
+    }
+}
+
+/// A `sasl_channel_binding_t` along with the data it points to.
+pub(crate) struct RawChannelBinding {
+    pub(crate) binding: sasl_channel_binding_t,
+    _name: CString,
+    _data: Box<[u8]>,
+}
+
+// SAFETY: the pointers refer only to data owned by the struct itself.
+unsafe impl Send for RawChannelBinding {}
+
+/// A hash function that a certificate signature may use.
+enum Hash {
+    Sha224,
+    Sha256,
+    Sha384,
+    Sha512,
+}
+
+// The DER encodings of the object identifiers of the signature algorithms
+// that determine a hash function.
+const MD5_WITH_RSA: &[u8] = &[0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x01, 0x04];
+const SHA1_WITH_RSA: &[u8] = &[0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x01, 0x05];
+const RSASSA_PSS: &[u8] = &[0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x01, 0x0a];
+const SHA256_WITH_RSA: &[u8] = &[0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x01, 0x0b];
+const SHA384_WITH_RSA: &[u8] = &[0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x01, 0x0c];
+const SHA512_WITH_RSA: &[u8] = &[0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x01, 0x0d];
+const SHA224_WITH_RSA: &[u8] = &[0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x01, 0x0e];
+const ECDSA_WITH_SHA1: &[u8] = &[0x2a, 0x86, 0x48, 0xce, 0x3d, 0x04, 0x01];
+const ECDSA_WITH_SHA224: &[u8] = &[0x2a, 0x86, 0x48, 0xce, 0x3d, 0x04, 0x03, 0x01];
+const ECDSA_WITH_SHA256: &[u8] = &[0x2a, 0x86, 0x48, 0xce, 0x3d, 0x04, 0x03, 0x02];
+const ECDSA_WITH_SHA384: &[u8] = &[0x2a, 0x86, 0x48, 0xce, 0x3d, 0x04, 0x03, 0x03];
+const ECDSA_WITH_SHA512: &[u8] = &[0x2a, 0x86, 0x48, 0xce, 0x3d, 0x04, 0x03, 0x04];
+const DSA_WITH_SHA1: &[u8] = &[0x2a, 0x86, 0x48, 0xce, 0x38, 0x04, 0x03];
+const DSA_WITH_SHA224: &[u8] = &[0x60, 0x86, 0x48, 0x01, 0x65, 0x03, 0x04, 0x03, 0x01];
+const DSA_WITH_SHA256: &[u8] = &[0x60, 0x86, 0x48, 0x01, 0x65, 0x03, 0x04, 0x03, 0x02];
+
+// The DER encodings of the object identifiers of the hash functions that an
+// RSASSA-PSS signature may use.
+const SHA1: &[u8] = &[0x2b, 0x0e, 0x03, 0x02, 0x1a];
+const SHA256: &[u8] = &[0x60, 0x86, 0x48, 0x01, 0x65, 0x03, 0x04, 0x02, 0x01];
+const SHA384: &[u8] = &[0x60, 0x86, 0x48, 0x01, 0x65, 0x03, 0x04, 0x02, 0x02];
+const SHA512: &[u8] = &[0x60, 0x86, 0x48, 0x01, 0x65, 0x03, 0x04, 0x02, 0x03];
+const SHA224: &[u8] = &[0x60, 0x86, 0x48, 0x01, 0x65, 0x03, 0x04, 0x02, 0x04];
+
+const TAG_SEQUENCE: u8 = 0x30;
+const TAG_OID: u8 = 0x06;
+const TAG_CONTEXT_0: u8 = 0xa0;
+
+/// Determines the hash function to use for the `tls-server-end-point`
+/// binding of a DER-encoded certificate.
+fn cert_hash(cert_der: &[u8]) -> Result<Hash, SaslError> {
+    let invalid = || SaslError::bad_param("invalid certificate");
+    // Certificate ::= SEQUENCE {
+    //     tbsCertificate       TBSCertificate,
+    //     signatureAlgorithm   AlgorithmIdentifier,
+    //     signatureValue       BIT STRING }
+    let (cert, _) = der_read(cert_der, TAG_SEQUENCE).ok_or_else(invalid)?;
+    let (_, rest) = der_read(cert, TAG_SEQUENCE).ok_or_else(invalid)?;
+    let (alg, _) = der_read(rest, TAG_SEQUENCE).ok_or_else(invalid)?;
+    // AlgorithmIdentifier ::= SEQUENCE {
+    //     algorithm    OBJECT IDENTIFIER,
+    //     parameters   ANY DEFINED BY algorithm OPTIONAL }
+    let (oid, params) = der_read(alg, TAG_OID).ok_or_else(invalid)?;
+    match oid {
+        MD5_WITH_RSA | SHA1_WITH_RSA | ECDSA_WITH_SHA1 | DSA_WITH_SHA1 => Ok(Hash::Sha256),
+        SHA224_WITH_RSA | ECDSA_WITH_SHA224 | DSA_WITH_SHA224 => Ok(Hash::Sha224),
+        SHA256_WITH_RSA | ECDSA_WITH_SHA256 | DSA_WITH_SHA256 => Ok(Hash::Sha256),
+        SHA384_WITH_RSA | ECDSA_WITH_SHA384 => Ok(Hash::Sha384),
+        SHA512_WITH_RSA | ECDSA_WITH_SHA512 => Ok(Hash::Sha512),
+        RSASSA_PSS => pss_hash(params).ok_or_else(invalid),
+        _ => Err(SaslError::bad_param(
+            "certificate signature algorithm does not determine a hash function",
+        )),
+    }
+}
+
+/// Determines the hash function of DER-encoded RSASSA-PSS parameters.
+fn pss_hash(params: &[u8]) -> Option<Hash> {
+    // RSASSA-PSS-params ::= SEQUENCE {
+    //     hashAlgorithm      [0] HashAlgorithm DEFAULT sha1,
+    //     ... }
+    let (params, _) = der_read(params, TAG_SEQUENCE)?;
+    let oid = match der_read(params, TAG_CONTEXT_0) {
+        None => SHA1,
+        Some((hash_alg, _)) => {
+            let (hash_alg, _) = der_read(hash_alg, TAG_SEQUENCE)?;
+            der_read(hash_alg, TAG_OID)?.0
+        }
+    };
+    match oid {
+        SHA1 | SHA256 => Some(Hash::Sha256),
+        SHA224 => Some(Hash::Sha224),
+        SHA384 => Some(Hash::Sha384),
+        SHA512 => Some(Hash::Sha512),
+        _ => None,
+    }
+}
+
+/// Reads a DER element with the given tag from the start of `input`,
+/// returning its contents and the remainder of `input`.
+fn der_read(input: &[u8], tag: u8) -> Option<(&[u8], &[u8])> {
+    let (&first, input) = input.split_first()?;
+    if first != tag {
+        return None;
+    }
+    let (&len, mut input) = input.split_first()?;
+    let len = if len < 0x80 {
+        usize::from(len)
+    } else {
+        // The long form gives the number of subsequent length bytes.
+        let n = usize::from(len & 0x7f);
+        if n == 0 || n > std::mem::size_of::<usize>() || input.len() < n {
+            return None;
+        }
+        let (bytes, rest) = input.split_at(n);
+        input = rest;
+        bytes.iter().fold(0, |len, &b| (len << 8) | usize::from(b))
+    };
+    if input.len() < len {
+        return None;
+    }
+    Some(input.split_at(len))
+}
@@ -0,0 +1,525 @@
+// Copyright Materialize, Inc. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License in the LICENSE file at the
+// root of this repository, or online at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Client connections.
+
+use std::collections::HashMap;
+use std::ffi::CString;
+use std::net::SocketAddr;
+use std::ptr;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use libc::{c_char, c_uint, c_ulong, c_void};
+use sasl2_sys::sasl::{
+    sasl_callback_t, sasl_client_new, sasl_client_start, sasl_client_step, sasl_conn_t,
+    sasl_dispose, sasl_interact_t, SASL_CB_AUTHNAME, SASL_CB_ECHOPROMPT, SASL_CB_GETREALM,
+    SASL_CB_LIST_END, SASL_CB_NOECHOPROMPT, SASL_CB_PASS, SASL_CB_USER, SASL_CHANNEL_BINDING,
+    SASL_MECHNAME, SASL_NEED_PROXY, SASL_OK, SASL_SUCCESS_DATA,
+};
+
+use crate::callbacks::Callbacks;
+use crate::channel_binding::ChannelBinding;
+use crate::connection::Connection;
+use crate::context::SaslContext;
+use crate::error::{self, SaslError, Status};
+use crate::interact::Prompts;
+use crate::mechanism::{MechanismPolicy, Selection};
+use crate::property::{self, RawValue, SecProps};
+use crate::security::SecurityProperties;
+use crate::util;
+
+/// The callback IDs for which the client is prepared to answer prompts
+/// interactively via [`Step::Interact`] if no callback is registered.
+const INTERACT_IDS: &[c_ulong] = &[
+    SASL_CB_USER,
+    SASL_CB_AUTHNAME,
+    SASL_CB_PASS,
+    SASL_CB_GETREALM,
+    SASL_CB_ECHOPROMPT,
+    SASL_CB_NOECHOPROMPT,
+];
+
+/// The source of the tokens that tie prompts to the round of interaction
+/// that produced them. Tokens are unique across all clients, so prompts from
+/// one client are never accepted by another.
+static NEXT_ROUND: AtomicU64 = AtomicU64::new(1);
+
+/// The outcome of a single step of an authentication exchange.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Step {
+    /// The exchange is not yet complete. The contained data must be sent to
+    /// the peer, and the peer's response supplied to the next step.
+    Continue(Vec<u8>),
+    /// The exchange completed successfully. If present, the contained data
+    /// must be sent to the peer.
+    Done(Option<Vec<u8>>),
+    /// The mechanism requires information from the user before it can
+    /// proceed. The prompts must be answered and passed to
+    /// [`SaslClient::interact`].
+    Interact(Prompts),
+}
+
+/// The call to repeat once the application has answered the prompts of a
+/// paused exchange.
+#[derive(Debug)]
+enum Pending {
+    Start(CString),
+    Step(Vec<u8>),
+}
+
+/// An answer to a prompt, kept alive for as long as the connection, as
+/// mechanisms may hold on to it.
+///
+/// The answer is nul-terminated, as some mechanisms treat it as a C string,
+/// and zeroed when dropped, as it may be a password.
+#[derive(Debug)]
+struct Answer(Box<[u8]>);
+
+impl Answer {
+    fn new(response: &[u8]) -> Answer {
+        let mut buf = Vec::with_capacity(response.len() + 1);
+        buf.extend_from_slice(response);
+        buf.push(0);
+        Answer(buf.into_boxed_slice())
+    }
+}
+
+impl Drop for Answer {
+    fn drop(&mut self) {
+        for b in self.0.iter_mut() {
+            unsafe { ptr::write_volatile(b, 0) }
+        }
+    }
+}
+
+/// A builder for a [`SaslClient`].
+#[derive(Debug)]
+pub struct ClientBuilder {
+    service: String,
+    server_fqdn: String,
+    local_addr: Option<SocketAddr>,
+    remote_addr: Option<SocketAddr>,
+    flags: c_uint,
+    callbacks: Callbacks,
+    security_properties: Option<SecurityProperties>,
+    channel_binding: Option<ChannelBinding>,
+    mechanism_policy: Option<MechanismPolicy>,
+    tls: bool,
+}
+
+impl ClientBuilder {
+    /// Sets the local address of the underlying transport.
+    ///
+    /// Some mechanisms, like KERBEROS_V4, require this to be set.
+    pub fn local_addr(mut self, addr: SocketAddr) -> ClientBuilder {
+        self.local_addr = Some(addr);
+        self
+    }
+
+    /// Sets the remote address of the underlying transport.
+    pub fn remote_addr(mut self, addr: SocketAddr) -> ClientBuilder {
+        self.remote_addr = Some(addr);
+        self
+    }
+
+    /// Sets the callbacks that supply information to the mechanism.
+    ///
+    /// Information for which no callback is registered, like the
+    /// authentication identity or password, is requested from the application
+    /// via [`Step::Interact`] instead.
+    pub fn callbacks(mut self, callbacks: Callbacks) -> ClientBuilder {
+        self.callbacks = callbacks;
+        self
+    }
+
+    /// Declares whether the application protocol allows the server to send
+    /// additional data along with its indication of success.
+    ///
+    /// This corresponds to the `SASL_SUCCESS_DATA` flag.
+    pub fn success_data(mut self, enabled: bool) -> ClientBuilder {
+        self.set_flag(SASL_SUCCESS_DATA, enabled);
+        self
+    }
+
+    /// Declares whether the application requires a mechanism that supports
+    /// proxy authorization.
+    ///
+    /// This corresponds to the `SASL_NEED_PROXY` flag.
+    pub fn need_proxy(mut self, enabled: bool) -> ClientBuilder {
+        self.set_flag(SASL_NEED_PROXY, enabled);
+        self
+    }
+
+    /// Sets the security properties that constrain the mechanisms the
+    /// connection will negotiate.
+    ///
+    /// If unset, libsasl2's defaults apply, which permit any mechanism but
+    /// disable security layers.
+    pub fn security_properties(mut self, props: SecurityProperties) -> ClientBuilder {
+        self.security_properties = Some(props);
+        self
+    }
+
+    /// Sets the channel binding data of the underlying secure channel.
+    ///
+    /// Mechanisms that support channel binding, like SCRAM-SHA-256-PLUS,
+    /// include it in the exchange.
+    pub fn channel_binding(mut self, binding: ChannelBinding) -> ClientBuilder {
+        self.channel_binding = Some(binding);
+        self
+    }
+
+    /// Sets the policy that chooses among the mechanisms offered by the
+    /// server in [`SaslClient::start`].
+    ///
+    /// If unset, libsasl2 chooses by its own heuristics.
+    pub fn mechanism_policy(mut self, policy: MechanismPolicy) -> ClientBuilder {
+        self.mechanism_policy = Some(policy);
+        self
+    }
+
+    /// Declares whether the underlying transport is secured by TLS.
+    ///
+    /// This is only consulted by the [mechanism
+    /// policy](ClientBuilder::mechanism_policy), for mechanisms that it
+    /// allows only over TLS.
+    pub fn tls(mut self, enabled: bool) -> ClientBuilder {
+        self.tls = enabled;
+        self
+    }
+
+    fn set_flag(&mut self, flag: c_uint, enabled: bool) {
+        if enabled {
+            self.flags |= flag;
+        } else {
+            self.flags &= !flag;
+        }
+    }
+
+    /// Creates the client connection.
+    pub fn build(self) -> Result<SaslClient, SaslError> {
+        let context = SaslContext::acquire_client()?;
+        let service = util::to_cstring(&self.service)?;
+        let server_fqdn = util::to_cstring(&self.server_fqdn)?;
+        let local_addr = self.local_addr.map(util::format_addr);
+        let remote_addr = self.remote_addr.map(util::format_addr);
+        self.callbacks.update_log_source(|source| {
+            source.service = Some(self.service.clone());
+            source.remote_addr = remote_addr
+                .as_ref()
+                .map(|addr| addr.to_string_lossy().into_owned());
+        });
+        let raw_callbacks = self.callbacks.to_raw(INTERACT_IDS);
+        let mut conn = ptr::null_mut();
+        let res = unsafe {
+            sasl_client_new(
+                service.as_ptr(),
+                server_fqdn.as_ptr(),
+                util::opt_ptr(&local_addr),
+                util::opt_ptr(&remote_addr),
+                raw_callbacks.as_ptr(),
+                self.flags,
+                &mut conn,
+            )
+        };
+        if res != SASL_OK {
+            // libsasl2 may allocate the connection even when it reports
+            // failure, in which case the connection holds the error detail.
+            let err = if conn.is_null() {
+                SaslError::from_code(res)
+            } else {
+                unsafe {
+                    let err = SaslError::from_conn(conn, res);
+                    sasl_dispose(&mut conn);
+                    err
+                }
+            };
+            return Err(err);
+        }
+        let mut client = SaslClient {
+            conn,
+            pending: None,
+            prompts: ptr::null_mut(),
+            round: 0,
+            answers: vec![],
+            callbacks: self.callbacks,
+            _raw_callbacks: raw_callbacks,
+            properties: HashMap::new(),
+            mechanism_policy: self.mechanism_policy,
+            tls: self.tls,
+            selection: None,
+            _context: context,
+        };
+        if let Some(props) = &self.security_properties {
+            client.set_property::<SecProps>(props)?;
+        }
+        if let Some(binding) = &self.channel_binding {
+            client.set_property::<property::ChannelBinding>(binding)?;
+        }
+        Ok(client)
+    }
+}
+
+/// A client connection.
+///
+/// The underlying libsasl2 connection is disposed of when the `SaslClient` is
+/// dropped.
+#[derive(Debug)]
+pub struct SaslClient {
+    conn: *mut sasl_conn_t,
+    // The paused call and libsasl2's prompt array, if the exchange is
+    // waiting on the application to answer prompts.
+    pending: Option<Pending>,
+    prompts: *mut sasl_interact_t,
+    round: u64,
+    answers: Vec<Answer>,
+    // libsasl2 retains pointers to the callback list, and the callback list
+    // to the callbacks' contexts, for the lifetime of the connection.
+    callbacks: Callbacks,
+    _raw_callbacks: Box<[sasl_callback_t]>,
+    // Property values that libsasl2 retains references to.
+    pub(crate) properties: HashMap<c_uint, RawValue>,
+    mechanism_policy: Option<MechanismPolicy>,
+    tls: bool,
+    selection: Option<Selection>,
+    // Keeps the library initialized until after the connection is disposed
+    // of.
+    _context: SaslContext,
+}
+
+// SAFETY: libsasl2 connections are not tied to the thread that created them,
+// and all access goes through `&mut self`.
+unsafe impl Send for SaslClient {}
+
+impl SaslClient {
+    /// Starts building a client connection for the named service (e.g.,
+    /// `"ldap"` or `"postgres"`) on the server with the given fully-qualified
+    /// domain name.
+    pub fn builder(service: &str, server_fqdn: &str) -> ClientBuilder {
+        ClientBuilder {
+            service: service.into(),
+            server_fqdn: server_fqdn.into(),
+            local_addr: None,
+            remote_addr: None,
+            flags: 0,
+            callbacks: Callbacks::new(),
+            security_properties: None,
+            channel_binding: None,
+            mechanism_policy: None,
+            tls: false,
+        }
+    }
+
+    /// Begins an authentication exchange.
+    ///
+    /// `mechlist` is the list of mechanisms offered by the server, separated
+    /// by spaces or commas. libsasl2 selects the most appropriate of the
+    /// mechanisms that are available on the client, which can be retrieved
+    /// afterwards with [`SaslClient::mechanism`].
+    ///
+    /// If a [mechanism policy](ClientBuilder::mechanism_policy) is set, the
+    /// policy chooses the mechanism instead, and returns a
+    /// [`SaslError::NoMech`] error if it finds none acceptable. Its reasoning
+    /// can be retrieved afterwards with [`SaslClient::selection`].
+    pub fn start(&mut self, mechlist: &str) -> Result<Step, SaslError> {
+        let mechlist = match &self.mechanism_policy {
+            None => util::to_cstring(mechlist)?,
+            Some(policy) => {
+                let available = self.list_mechanisms(None)?;
+                let channel_binding = self.properties.contains_key(&SASL_CHANNEL_BINDING);
+                let offers_plus = mechlist
+                    .split([' ', ','])
+                    .any(|m| m.to_ascii_uppercase().ends_with("-PLUS"));
+                let selection = policy.select(mechlist, &available, self.tls, channel_binding);
+                let selection = self.selection.insert(selection);
+                let mech = match &selection.selected {
+                    Some(mech) => mech,
+                    None => return Err(SaslError::no_mech(&selection.to_string())),
+                };
+                // libsasl2 treats a single mechanism as an explicit choice
+                // rather than a negotiation, and then does not tell the
+                // server that the client supports channel binding. The
+                // server relies on that to detect -PLUS mechanisms stripped
+                // from its advertisement, so unless the server advertised
+                // some, the chosen mechanism is offered twice to make
+                // libsasl2 negotiate.
+                if channel_binding && !offers_plus {
+                    util::to_cstring(&format!("{} {}", mech, mech))?
+                } else {
+                    util::to_cstring(mech)?
+                }
+            }
+        };
+        self.exchange(Pending::Start(mechlist), ptr::null_mut())
+    }
+
+    /// Advances an authentication exchange with data received from the
+    /// server.
+    pub fn step(&mut self, server_data: &[u8]) -> Result<Step, SaslError> {
+        util::buf_len(server_data)?;
+        self.exchange(Pending::Step(server_data.into()), ptr::null_mut())
+    }
+
+    /// Resumes an authentication exchange that paused with
+    /// [`Step::Interact`], supplying the answers to its prompts.
+    ///
+    /// The answers are kept alive by the connection until it is dropped, and
+    /// are zeroed when it is.
+    ///
+    /// Returns an error if `prompts` were not produced by the most recent
+    /// step of this connection, or if an answer is too long. The exchange
+    /// remains paused in that case, and can be resumed with valid prompts.
+    pub fn interact(&mut self, prompts: Prompts) -> Result<Step, SaslError> {
+        if self.pending.is_none() || prompts.round != self.round {
+            return Err(SaslError::bad_param(
+                "prompts do not belong to the paused exchange",
+            ));
+        }
+        let raw = self.prompts;
+        // The prompts are written back into libsasl2's prompt array by
+        // position, so they must match it exactly.
+        // SAFETY: libsasl2's prompt array remains valid until the next call
+        // on the connection.
+        let ids = unsafe { prompt_ids(raw) };
+        if !ids.iter().copied().eq(prompts.iter().map(|p| p.id())) {
+            return Err(SaslError::bad_param(
+                "prompts do not match the paused exchange",
+            ));
+        }
+        // Check every answer before touching the prompt array, so that the
+        // exchange can be resumed again if one is rejected.
+        for response in prompts.iter().filter_map(|p| p.response()) {
+            util::buf_len(response)?;
+        }
+        let pending = self.pending.take().unwrap();
+        self.prompts = ptr::null_mut();
+        for (i, prompt) in prompts.iter().enumerate() {
+            // SAFETY: the array has an entry for each prompt, as checked
+            // above.
+            let entry = unsafe { &mut *raw.add(i) };
+            match prompt.response() {
+                None => {
+                    entry.result = ptr::null();
+                    entry.len = 0;
+                }
+                Some(response) => {
+                    let answer = Answer::new(response);
+                    entry.result = answer.0.as_ptr() as *const c_void;
+                    entry.len = response.len() as c_uint;
+                    self.answers.push(answer);
+                }
+            }
+        }
+        self.exchange(pending, raw)
+    }
+
+    /// Returns the name of the selected mechanism, if an exchange has been
+    /// started.
+    pub fn mechanism(&self) -> Option<String> {
+        unsafe { util::getprop_str(self.conn, SASL_MECHNAME) }
+    }
+
+    /// Returns the outcome of the mechanism policy's most recent choice, if a
+    /// policy is set and an exchange has been started.
+    pub fn selection(&self) -> Option<&Selection> {
+        self.selection.as_ref()
+    }
+
+    /// Returns a pointer to the underlying libsasl2 connection.
+    ///
+    /// This is an escape hatch for functionality that is not yet exposed
+    /// safely. The pointer remains owned by the `SaslClient`, and must not be
+    /// disposed of or used after the `SaslClient` is dropped.
+    pub fn as_ptr(&self) -> *mut sasl_conn_t {
+        self.conn
+    }
+
+    /// Calls `sasl_client_start` or `sasl_client_step`, resuming from the
+    /// answered prompt array `prompts` if it is not null.
+    fn exchange(
+        &mut self,
+        call: Pending,
+        mut prompts: *mut sasl_interact_t,
+    ) -> Result<Step, SaslError> {
+        let mut out = ptr::null();
+        let mut outlen = 0;
+        let res = match &call {
+            Pending::Start(mechlist) => {
+                let mut mech = ptr::null();
+                let res = unsafe {
+                    sasl_client_start(
+                        self.conn,
+                        mechlist.as_ptr(),
+                        &mut prompts,
+                        &mut out,
+                        &mut outlen,
+                        &mut mech,
+                    )
+                };
+                if let Some(mech) = unsafe { util::copy_str(mech) } {
+                    self.callbacks
+                        .update_log_source(|source| source.mechanism = Some(mech));
+                }
+                res
+            }
+            Pending::Step(server_data) => unsafe {
+                sasl_client_step(
+                    self.conn,
+                    server_data.as_ptr() as *const c_char,
+                    server_data.len() as c_uint,
+                    &mut prompts,
+                    &mut out,
+                    &mut outlen,
+                )
+            },
+        };
+        self.pending = None;
+        self.prompts = ptr::null_mut();
+        match unsafe { error::check(self.conn, res)? } {
+            Status::Ok if out.is_null() => Ok(Step::Done(None)),
+            Status::Ok => Ok(Step::Done(Some(unsafe { util::copy_buf(out, outlen) }))),
+            Status::Continue => Ok(Step::Continue(unsafe { util::copy_buf(out, outlen) })),
+            Status::Interact => {
+                self.round = NEXT_ROUND.fetch_add(1, Ordering::Relaxed);
+                self.pending = Some(call);
+                self.prompts = prompts;
+                Ok(Step::Interact(unsafe {
+                    Prompts::from_raw(prompts, self.round)
+                }))
+            }
+        }
+    }
+}
+
+/// Returns the IDs of the prompts in a `SASL_CB_LIST_END`-terminated
+/// `sasl_interact_t` array.
+///
+/// # Safety
+///
+/// `raw` must be null or point to a valid prompt array.
+unsafe fn prompt_ids(mut raw: *const sasl_interact_t) -> Vec<c_ulong> {
+    let mut ids = vec![];
+    while !raw.is_null() && (*raw).id != SASL_CB_LIST_END {
+        ids.push((*raw).id);
+        raw = raw.add(1);
+    }
+    ids
+}
+
+impl Drop for SaslClient {
+    fn drop(&mut self) {
+        unsafe { sasl_dispose(&mut self.conn) }
+    }
+}
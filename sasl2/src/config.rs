@@ -0,0 +1,173 @@
+// Copyright Materialize, Inc. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License in the LICENSE file at the
+// root of this repository, or online at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Programmatic configuration.
+//!
+//! libsasl2 and its plugins read options like `mech_list` from
+//! `<appname>.conf` files, consulting the `SASL_CB_GETOPT` callback first.
+//! [`SaslConfig`] collects options in Rust and serves them through that
+//! callback, via [`Callbacks::config`](crate::callbacks::Callbacks::config),
+//! so no configuration file is required.
+//!
+//! With the `serde` feature, `SaslConfig` implements `Deserialize`, so it can
+//! be embedded in an application's own configuration:
+//!
+//! ```toml
+//! mech_list = ["SCRAM-SHA-256", "PLAIN"]
+//! pwcheck_method = ["auxprop"]
+//! scram_iteration_counter = 8192
+//!
+//! [options]
+//! log_level = "3"
+//!
+//! [plugins.SCRAM-SHA-256]
+//! scram_iteration_counter = "16384"
+//! ```
+//!
+//! Registered as a connection callback, the configuration applies to that
+//! connection only; registered as a global callback, it applies to all
+//! connections, and to options read during library initialization.
+//!
+//! ```no_run
+//! use sasl2::callbacks::Callbacks;
+//! use sasl2::config::SaslConfig;
+//! use sasl2::server::SaslServer;
+//!
+//! let config = SaslConfig::new()
+//!     .mech_list(["SCRAM-SHA-256"])
+//!     .scram_iteration_counter(16384);
+//! let server = SaslServer::builder("postgres")
+//!     .callbacks(Callbacks::new().config(config))
+//!     .build()?;
+//! # Ok::<(), sasl2::error::SaslError>(())
+//! ```
+
+use std::collections::BTreeMap;
+
+/// A set of libsasl2 configuration options.
+///
+/// Options that are set on a plugin take precedence over the typed options,
+/// which in turn take precedence over free-form global options.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Deserialize),
+    serde(default, deny_unknown_fields)
+)]
+pub struct SaslConfig {
+    mech_list: Option<Vec<String>>,
+    pwcheck_method: Option<Vec<String>>,
+    auxprop_plugin: Option<Vec<String>>,
+    scram_iteration_counter: Option<u32>,
+    canon_user_plugin: Option<String>,
+    options: BTreeMap<String, String>,
+    plugins: BTreeMap<String, BTreeMap<String, String>>,
+}
+
+impl SaslConfig {
+    /// Creates an empty configuration.
+    pub fn new() -> SaslConfig {
+        SaslConfig::default()
+    }
+
+    /// Sets the mechanisms that servers offer (`mech_list`).
+    ///
+    /// If unset, all available mechanisms are offered.
+    pub fn mech_list<I, S>(mut self, mechs: I) -> SaslConfig
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.mech_list = Some(mechs.into_iter().map(Into::into).collect());
+        self
+    }
+
+    /// Sets the methods that are tried, in order, to verify plaintext
+    /// passwords (`pwcheck_method`), e.g., `"auxprop"` or `"saslauthd"`.
+    pub fn pwcheck_method<I, S>(mut self, methods: I) -> SaslConfig
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.pwcheck_method = Some(methods.into_iter().map(Into::into).collect());
+        self
+    }
+
+    /// Sets the auxiliary property plugins that are consulted, in order, for
+    /// user properties like passwords (`auxprop_plugin`), e.g., `"sasldb"`.
+    pub fn auxprop_plugin<I, S>(mut self, plugins: I) -> SaslConfig
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.auxprop_plugin = Some(plugins.into_iter().map(Into::into).collect());
+        self
+    }
+
+    /// Sets the number of iterations that SCRAM mechanisms use when hashing
+    /// new passwords (`scram_iteration_counter`).
+    pub fn scram_iteration_counter(mut self, iterations: u32) -> SaslConfig {
+        self.scram_iteration_counter = Some(iterations);
+        self
+    }
+
+    /// Sets the plugin that canonicalizes usernames (`canon_user_plugin`).
+    pub fn canon_user_plugin(mut self, plugin: &str) -> SaslConfig {
+        self.canon_user_plugin = Some(plugin.into());
+        self
+    }
+
+    /// Sets an arbitrary option for libsasl2 and all plugins.
+    pub fn option(mut self, name: &str, value: &str) -> SaslConfig {
+        self.options.insert(name.into(), value.into());
+        self
+    }
+
+    /// Sets an arbitrary option for the named plugin only, e.g., for
+    /// `"SCRAM-SHA-256"`.
+    ///
+    /// Plugin names are matched case-insensitively.
+    pub fn plugin_option(mut self, plugin: &str, name: &str, value: &str) -> SaslConfig {
+        self.plugins
+            .entry(plugin.into())
+            .or_default()
+            .insert(name.into(), value.into());
+        self
+    }
+
+    /// Returns the value of the named option, as requested by the named
+    /// plugin or, if `plugin` is `None`, by libsasl2 itself.
+    pub fn get(&self, plugin: Option<&str>, name: &str) -> Option<String> {
+        if let Some(plugin) = plugin {
+            let value = self
+                .plugins
+                .iter()
+                .filter(|(p, _)| p.eq_ignore_ascii_case(plugin))
+                .find_map(|(_, options)| options.get(name));
+            if let Some(value) = value {
+                return Some(value.clone());
+            }
+        }
+        let typed = match name {
+            "mech_list" => self.mech_list.as_ref().map(|v| v.join(" ")),
+            "pwcheck_method" => self.pwcheck_method.as_ref().map(|v| v.join(" ")),
+            "auxprop_plugin" => self.auxprop_plugin.as_ref().map(|v| v.join(" ")),
+            "scram_iteration_counter" => self.scram_iteration_counter.map(|n| n.to_string()),
+            "canon_user_plugin" => self.canon_user_plugin.clone(),
+            _ => None,
+        };
+        typed.or_else(|| self.options.get(name).cloned())
+    }
+}
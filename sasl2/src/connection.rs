@@ -0,0 +1,244 @@
+// Copyright Materialize, Inc. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License in the LICENSE file at the
+// root of this repository, or online at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Functionality common to client and server connections.
+
+use std::convert::TryFrom;
+use std::io::IoSlice;
+use std::ptr;
+
+use libc::{c_char, c_int, c_uint};
+use sasl2_sys::sasl::{
+    sasl_conn_t, sasl_decode, sasl_encode, sasl_encodev, sasl_getprop, sasl_listmech, sasl_setprop,
+    SASL_BADPARAM, SASL_NOMECH, SASL_NOTDONE,
+};
+
+use crate::client::SaslClient;
+use crate::error::{self, SaslError};
+use crate::property::{GetProperty, RawValue, SetProperty};
+use crate::server::SaslServer;
+use crate::session::SessionInfo;
+use crate::util;
+
+mod private {
+    use libc::c_uint;
+
+    use crate::property::RawValue;
+
+    pub trait Sealed {
+        /// Keeps the value of property `id` alive for as long as the
+        /// connection, replacing any previously retained value for the same
+        /// property.
+        fn retain(&mut self, id: c_uint, value: RawValue);
+    }
+}
+
+/// A client or server connection.
+///
+/// This trait is sealed, and is implemented only by [`SaslClient`] and
+/// [`SaslServer`].
+pub trait Connection: private::Sealed {
+    /// Returns a pointer to the underlying libsasl2 connection.
+    ///
+    /// See [`SaslClient::as_ptr`] and [`SaslServer::as_ptr`].
+    fn as_ptr(&self) -> *mut sasl_conn_t;
+
+    /// Reads the property `P`.
+    ///
+    /// Returns a [`SaslError::NotDone`] error if the property has no value,
+    /// e.g., because the exchange has not yet completed.
+    fn get_property<P>(&self) -> Result<P::Value, SaslError>
+    where
+        P: GetProperty,
+    {
+        let conn = self.as_ptr();
+        let mut value = ptr::null();
+        unsafe {
+            let res = sasl_getprop(conn, P::ID as c_int, &mut value);
+            error::check(conn, res)?;
+            if value.is_null() {
+                return Err(SaslError::from_code(SASL_NOTDONE));
+            }
+            Ok(P::from_raw(value))
+        }
+    }
+
+    /// Writes the property `P`.
+    ///
+    /// If libsasl2 retains a reference to the value rather than copying it,
+    /// the connection keeps the value alive until the property is next
+    /// written or the connection is dropped.
+    fn set_property<P>(&mut self, value: &P::Value) -> Result<(), SaslError>
+    where
+        P: SetProperty,
+    {
+        let conn = self.as_ptr();
+        let raw = P::to_raw(value)?;
+        unsafe {
+            let res = sasl_setprop(conn, P::ID as c_int, raw.ptr);
+            error::check(conn, res)?;
+        }
+        if raw.retained {
+            self.retain(P::ID, raw);
+        }
+        Ok(())
+    }
+
+    /// Returns information about the completed exchange, like whether
+    /// channel binding was used.
+    ///
+    /// Returns a [`SaslError::NotDone`] error if the exchange has not
+    /// completed. Returns a [`SaslError::BadVers`] error unless the linked
+    /// libsasl2 is release 2.1.28, the one that sasl2-sys vendors, because
+    /// the information is read from the library's internals.
+    fn session_info(&self) -> Result<SessionInfo, SaslError> {
+        unsafe { SessionInfo::from_conn(self.as_ptr()) }
+    }
+
+    /// Lists the mechanisms that the connection may negotiate, given its
+    /// security properties (`sasl_listmech`).
+    ///
+    /// `user` names the user who is about to authenticate, so that the list
+    /// can be tailored to them, but current versions of libsasl2 ignore it.
+    fn list_mechanisms(&self, user: Option<&str>) -> Result<Vec<String>, SaslError> {
+        let conn = self.as_ptr();
+        let user = user.map(util::to_cstring).transpose()?;
+        let mut result = ptr::null();
+        unsafe {
+            let res = sasl_listmech(
+                conn,
+                util::opt_ptr(&user),
+                ptr::null(),
+                b" \0".as_ptr() as *const c_char,
+                ptr::null(),
+                &mut result,
+                ptr::null_mut(),
+                ptr::null_mut(),
+            );
+            // Clients report that no mechanism is available as an error.
+            if res == SASL_NOMECH {
+                return Ok(vec![]);
+            }
+            error::check(conn, res)?;
+            Ok(util::copy_str(result)
+                .map(|list| {
+                    list.split(' ')
+                        .filter(|m| !m.is_empty())
+                        .map(Into::into)
+                        .collect()
+                })
+                .unwrap_or_default())
+        }
+    }
+
+    /// Encodes data for transmission to the peer using the negotiated
+    /// security layer.
+    ///
+    /// The output includes any framing required by the mechanism, and can be
+    /// written to the transport as-is.
+    fn encode(&mut self, data: &[u8]) -> Result<Vec<u8>, SaslError> {
+        let conn = self.as_ptr();
+        let len = util::buf_len(data)?;
+        let mut out = ptr::null();
+        let mut outlen = 0;
+        unsafe {
+            let res = sasl_encode(
+                conn,
+                data.as_ptr() as *const c_char,
+                len,
+                &mut out,
+                &mut outlen,
+            );
+            error::check(conn, res)?;
+            Ok(util::copy_buf(out, outlen))
+        }
+    }
+
+    /// Encodes the concatenation of `bufs` for transmission to the peer using
+    /// the negotiated security layer.
+    ///
+    /// This is equivalent to, but avoids the copy involved in, concatenating
+    /// `bufs` and passing the result to [`Connection::encode`]. If the input
+    /// exceeds the peer's maximum buffer size (`SASL_MAXOUTBUF`), libsasl2
+    /// splits it across as many frames as necessary, and the output contains
+    /// all of them.
+    fn encode_vectored(&mut self, bufs: &[IoSlice<'_>]) -> Result<Vec<u8>, SaslError> {
+        let conn = self.as_ptr();
+        // libsasl2 rejects an empty vector.
+        if bufs.is_empty() {
+            return Ok(vec![]);
+        }
+        let vecs = bufs
+            .iter()
+            .map(util::to_iovec)
+            .collect::<Result<Vec<_>, _>>()?;
+        let numiov =
+            c_uint::try_from(vecs.len()).map_err(|_| SaslError::from_code(SASL_BADPARAM))?;
+        let mut out = ptr::null();
+        let mut outlen = 0;
+        unsafe {
+            let res = sasl_encodev(conn, vecs.as_ptr(), numiov, &mut out, &mut outlen);
+            error::check(conn, res)?;
+            Ok(util::copy_buf(out, outlen))
+        }
+    }
+
+    /// Decodes data received from the peer using the negotiated security
+    /// layer.
+    ///
+    /// Mechanisms buffer incomplete input internally, so the output may be
+    /// empty if `data` does not complete a frame.
+    fn decode(&mut self, data: &[u8]) -> Result<Vec<u8>, SaslError> {
+        let conn = self.as_ptr();
+        let len = util::buf_len(data)?;
+        let mut out = ptr::null();
+        let mut outlen: c_uint = 0;
+        unsafe {
+            let res = sasl_decode(
+                conn,
+                data.as_ptr() as *const c_char,
+                len,
+                &mut out,
+                &mut outlen,
+            );
+            error::check(conn, res)?;
+            Ok(util::copy_buf(out, outlen))
+        }
+    }
+}
+
+impl private::Sealed for SaslClient {
+    fn retain(&mut self, id: c_uint, value: RawValue) {
+        self.properties.insert(id, value);
+    }
+}
+
+impl Connection for SaslClient {
+    fn as_ptr(&self) -> *mut sasl_conn_t {
+        SaslClient::as_ptr(self)
+    }
+}
+
+impl private::Sealed for SaslServer {
+    fn retain(&mut self, id: c_uint, value: RawValue) {
+        self.properties.insert(id, value);
+    }
+}
+
+impl Connection for SaslServer {
+    fn as_ptr(&self) -> *mut sasl_conn_t {
+        SaslServer::as_ptr(self)
+    }
+}
@@ -0,0 +1,340 @@
+// Copyright Materialize, Inc. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License in the LICENSE file at the
+// root of this repository, or online at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Library initialization.
+//!
+//! libsasl2 must be initialized before any connection is created, separately
+//! for clients (`sasl_client_init`) and servers (`sasl_server_init`), and
+//! torn down (`sasl_client_done`, `sasl_server_done`) once it is no longer
+//! needed. These functions manipulate process-global state and are not
+//! thread safe.
+//!
+//! A [`SaslContext`] is a reference-counted handle to one side of the
+//! initialized library. The first handle for a side initializes it, and
+//! dropping the last handle tears it down. Connections hold a handle for
+//! their lifetime, so applications only need to create a context explicitly
+//! to register global callbacks, to set the application name, or to keep the
+//! library initialized between connections.
+//!
+//! [`set_plugin_path`] and [`set_config_path`] control where libsasl2 looks
+//! for plugins and configuration files when it is initialized.
+//!
+//! ```no_run
+//! use sasl2::callbacks::Callbacks;
+//! use sasl2::context::SaslContext;
+//!
+//! let callbacks = Callbacks::new().log(|level, message| eprintln!("{:?}: {}", level, message));
+//! let context = SaslContext::builder()
+//!     .callbacks(callbacks)
+//!     .server("myapp")?;
+//! # Ok::<(), sasl2::error::SaslError>(())
+//! ```
+
+use std::ffi::CString;
+use std::path::Path;
+use std::ptr;
+use std::sync::Mutex;
+
+use sasl2_sys::sasl::{
+    sasl_callback_t, sasl_client_done, sasl_client_init, sasl_server_done, sasl_server_init,
+    sasl_set_path, SASL_OK, SASL_PATH_TYPE_CONFIG, SASL_PATH_TYPE_PLUGIN,
+};
+
+use crate::callbacks::Callbacks;
+use crate::error::SaslError;
+use crate::util;
+
+/// The state of the initialized library, guarded by a lock so that
+/// initialization and teardown are serialized.
+static STATE: Mutex<State> = Mutex::new(State {
+    initialized: false,
+    plugin_path: None,
+    config_path: None,
+    client: None,
+    server: None,
+});
+
+struct State {
+    // Whether either side of the library has ever been initialized, after
+    // which libsasl2 ignores attempts to replace its allocation and locking
+    // functions.
+    initialized: bool,
+    // libsasl2 forgets its search paths when it is torn down, so they are
+    // reapplied before every initialization.
+    plugin_path: Option<CString>,
+    config_path: Option<CString>,
+    client: Option<Global>,
+    server: Option<Global>,
+}
+
+impl State {
+    fn side(&mut self, side: Side) -> &mut Option<Global> {
+        match side {
+            Side::Client => &mut self.client,
+            Side::Server => &mut self.server,
+        }
+    }
+}
+
+/// One initialized side of the library.
+struct Global {
+    refs: usize,
+    // libsasl2 retains pointers to the application name and the global
+    // callback list until the library is torn down.
+    appname: Option<CString>,
+    _callbacks: Option<(Callbacks, Box<[sasl_callback_t]>)>,
+}
+
+// SAFETY: the raw callback list refers only to the contexts of the owned
+// callbacks, which are `Send`.
+unsafe impl Send for Global {}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Side {
+    Client,
+    Server,
+}
+
+/// A handle to the client or server side of the initialized library.
+///
+/// Cloning the handle increments the reference count, and the side is torn
+/// down when the last handle is dropped.
+///
+/// Initialization is expensive: libsasl2 loads every plugin in the plugin
+/// path and, on servers, reads the configuration file. Because connections
+/// hold handles too, an application that creates connections one at a time
+/// without holding a handle of its own pays this cost for every connection.
+/// Such applications should create a context up front and keep it for as
+/// long as they make connections.
+#[derive(Debug)]
+pub struct SaslContext {
+    side: Side,
+}
+
+impl SaslContext {
+    /// Returns a builder for a context with global callbacks.
+    pub fn builder() -> ContextBuilder {
+        ContextBuilder { callbacks: None }
+    }
+
+    /// Returns a handle to the client side of the library, initializing it
+    /// if necessary.
+    pub fn client() -> Result<SaslContext, SaslError> {
+        SaslContext::builder().client()
+    }
+
+    /// Returns a handle to the server side of the library, initializing it
+    /// with the given application name if necessary.
+    ///
+    /// The application name determines the name of the configuration file
+    /// that libsasl2 reads, e.g., `myapp.conf`. Returns a
+    /// [`SaslError::BadParam`] error if the server side is already initialized
+    /// with a different application name.
+    pub fn server(appname: &str) -> Result<SaslContext, SaslError> {
+        SaslContext::builder().server(appname)
+    }
+
+    /// Returns a handle to the client side of the library for a new
+    /// connection.
+    pub(crate) fn acquire_client() -> Result<SaslContext, SaslError> {
+        SaslContext::acquire(Side::Client, None, None)
+    }
+
+    /// Returns a handle to the server side of the library for a new
+    /// connection, initializing it without an application name if necessary.
+    pub(crate) fn acquire_server() -> Result<SaslContext, SaslError> {
+        SaslContext::acquire(Side::Server, None, None)
+    }
+
+    fn acquire(
+        side: Side,
+        appname: Option<&str>,
+        callbacks: Option<Callbacks>,
+    ) -> Result<SaslContext, SaslError> {
+        let mut state = STATE.lock().unwrap_or_else(|e| e.into_inner());
+        // libsasl2 locks its allocation and locking functions as soon as
+        // initialization begins, even if it goes on to fail.
+        state.initialized = true;
+        let paths = [
+            (SASL_PATH_TYPE_PLUGIN, state.plugin_path.clone()),
+            (SASL_PATH_TYPE_CONFIG, state.config_path.clone()),
+        ];
+        let global = state.side(side);
+        if let Some(global) = global {
+            if callbacks.is_some() {
+                return Err(SaslError::bad_param(
+                    "library already initialized; global callbacks cannot be changed",
+                ));
+            }
+            if let Some(appname) = appname {
+                if global.appname.as_deref().map(|a| a.to_bytes()) != Some(appname.as_bytes()) {
+                    return Err(SaslError::bad_param(
+                        "library already initialized with a different application name",
+                    ));
+                }
+            }
+            global.refs += 1;
+            return Ok(SaslContext { side });
+        }
+
+        let appname = appname.map(util::to_cstring).transpose()?;
+        let callbacks = callbacks.map(|callbacks| {
+            let raw = callbacks.to_raw(&[]);
+            (callbacks, raw)
+        });
+        let raw_callbacks = match &callbacks {
+            None => ptr::null(),
+            Some((_, raw)) => raw.as_ptr(),
+        };
+        for (path_type, path) in &paths {
+            if let Some(path) = path {
+                // libsasl2 copies the path.
+                let res = unsafe { sasl_set_path(*path_type, path.as_ptr() as *mut _) };
+                if res != SASL_OK {
+                    return Err(SaslError::from_code(res));
+                }
+            }
+        }
+        let res = unsafe {
+            match side {
+                Side::Client => sasl_client_init(raw_callbacks),
+                Side::Server => sasl_server_init(raw_callbacks, util::opt_ptr(&appname)),
+            }
+        };
+        if res != SASL_OK {
+            return Err(SaslError::from_code(res));
+        }
+        *global = Some(Global {
+            refs: 1,
+            appname,
+            _callbacks: callbacks,
+        });
+        Ok(SaslContext { side })
+    }
+}
+
+/// Sets the directories that are searched for plugins, in place of the
+/// compiled-in default (`sasl_set_path` with `SASL_PATH_TYPE_PLUGIN`).
+///
+/// Plugins are loaded when the library is initialized, so the path takes
+/// effect the next time either side is initialized.
+pub fn set_plugin_path<I, P>(paths: I) -> Result<(), SaslError>
+where
+    I: IntoIterator<Item = P>,
+    P: AsRef<Path>,
+{
+    let path = util::join_paths(paths)?;
+    let mut state = STATE.lock().unwrap_or_else(|e| e.into_inner());
+    state.plugin_path = Some(path);
+    Ok(())
+}
+
+/// Sets the directories that are searched for the server's configuration
+/// file, `<appname>.conf`, in place of the compiled-in default
+/// (`sasl_set_path` with `SASL_PATH_TYPE_CONFIG`).
+///
+/// The configuration file is read when the server side is initialized, so
+/// the path takes effect the next time it is initialized.
+pub fn set_config_path<I, P>(paths: I) -> Result<(), SaslError>
+where
+    I: IntoIterator<Item = P>,
+    P: AsRef<Path>,
+{
+    let path = util::join_paths(paths)?;
+    let mut state = STATE.lock().unwrap_or_else(|e| e.into_inner());
+    state.config_path = Some(path);
+    Ok(())
+}
+
+/// Runs `f`, which configures process-global libsasl2 hooks, provided that
+/// the library has never been initialized.
+///
+/// The library cannot be initialized while `f` runs.
+pub(crate) fn configure<F>(f: F) -> Result<(), SaslError>
+where
+    F: FnOnce() -> Result<(), SaslError>,
+{
+    let state = STATE.lock().unwrap_or_else(|e| e.into_inner());
+    if state.initialized {
+        return Err(SaslError::bad_param(
+            "library already initialized; hooks must be installed beforehand",
+        ));
+    }
+    f()
+}
+
+impl Clone for SaslContext {
+    fn clone(&self) -> SaslContext {
+        let mut state = STATE.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(global) = state.side(self.side) {
+            global.refs += 1;
+        }
+        SaslContext { side: self.side }
+    }
+}
+
+impl Drop for SaslContext {
+    fn drop(&mut self) {
+        let mut state = STATE.lock().unwrap_or_else(|e| e.into_inner());
+        let global = state.side(self.side);
+        if let Some(g) = global {
+            g.refs -= 1;
+            if g.refs == 0 {
+                unsafe {
+                    match self.side {
+                        Side::Client => sasl_client_done(),
+                        Side::Server => sasl_server_done(),
+                    };
+                }
+                // The callbacks and application name are no longer
+                // referenced by libsasl2.
+                *global = None;
+            }
+        }
+    }
+}
+
+/// A builder for a [`SaslContext`].
+#[derive(Debug)]
+pub struct ContextBuilder {
+    callbacks: Option<Callbacks>,
+}
+
+impl ContextBuilder {
+    /// Sets the global callbacks, which libsasl2 consults for all connections
+    /// that do not register a callback with the same ID themselves.
+    ///
+    /// Global callbacks can only be registered by the handle that initializes
+    /// the library. Building a context with callbacks when the side is
+    /// already initialized returns a [`SaslError::BadParam`] error.
+    pub fn callbacks(mut self, callbacks: Callbacks) -> ContextBuilder {
+        self.callbacks = Some(callbacks);
+        self
+    }
+
+    /// Returns a handle to the client side of the library, initializing it
+    /// if necessary.
+    pub fn client(self) -> Result<SaslContext, SaslError> {
+        SaslContext::acquire(Side::Client, None, self.callbacks)
+    }
+
+    /// Returns a handle to the server side of the library, initializing it
+    /// with the given application name if necessary.
+    ///
+    /// See [`SaslContext::server`].
+    pub fn server(self, appname: &str) -> Result<SaslContext, SaslError> {
+        SaslContext::acquire(Side::Server, Some(appname), self.callbacks)
+    }
+}
@@ -0,0 +1,295 @@
+// Copyright Materialize, Inc. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License in the LICENSE file at the
+// root of this repository, or online at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Error handling.
+
+use std::error::Error;
+use std::fmt;
+use std::io;
+use std::ptr;
+
+use libc::c_int;
+use sasl2_sys::sasl::*;
+
+use crate::util;
+
+/// Additional information about a [`SaslError`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ErrorContext {
+    description: String,
+    detail: Option<String>,
+}
+
+impl ErrorContext {
+    /// Returns the generic description of the result code, as reported by
+    /// `sasl_errstring`.
+    pub fn description(&self) -> &str {
+        &self.description
+    }
+
+    /// Returns the detailed message recorded on the connection at the point
+    /// of failure, as reported by `sasl_errdetail`, if one was available.
+    pub fn detail(&self) -> Option<&str> {
+        self.detail.as_deref()
+    }
+}
+
+macro_rules! sasl_errors {
+    ($($(#[$meta:meta])* $variant:ident => $code:ident,)*) => {
+        /// An error returned by libsasl2.
+        ///
+        /// Each variant corresponds to one of the `SASL_*` result codes.
+        #[non_exhaustive]
+        #[derive(Debug, Clone, PartialEq, Eq)]
+        pub enum SaslError {
+            $($(#[$meta])* $variant(ErrorContext),)*
+            /// A result code that this crate does not recognize.
+            Unknown(c_int, ErrorContext),
+        }
+
+        impl SaslError {
+            fn with_context(code: c_int, context: ErrorContext) -> SaslError {
+                match code {
+                    $($code => SaslError::$variant(context),)*
+                    _ => SaslError::Unknown(code, context),
+                }
+            }
+
+            /// Returns the raw libsasl2 result code.
+            pub fn code(&self) -> c_int {
+                match self {
+                    $(SaslError::$variant(_) => $code,)*
+                    SaslError::Unknown(code, _) => *code,
+                }
+            }
+
+            /// Returns additional information about the error.
+            pub fn context(&self) -> &ErrorContext {
+                match self {
+                    $(SaslError::$variant(context) => context,)*
+                    SaslError::Unknown(_, context) => context,
+                }
+            }
+        }
+    };
+}
+
+sasl_errors! {
+    /// Generic failure.
+    Fail => SASL_FAIL,
+    /// Memory shortage failure.
+    NoMem => SASL_NOMEM,
+    /// Overflowed buffer.
+    BufOver => SASL_BUFOVER,
+    /// Mechanism not supported.
+    NoMech => SASL_NOMECH,
+    /// Bad protocol or cancellation.
+    BadProt => SASL_BADPROT,
+    /// Information was requested before it was available in the exchange.
+    NotDone => SASL_NOTDONE,
+    /// Invalid parameter supplied.
+    BadParam => SASL_BADPARAM,
+    /// Transient failure, e.g., a weak key.
+    TryAgain => SASL_TRYAGAIN,
+    /// Integrity check failed.
+    BadMac => SASL_BADMAC,
+    /// The library is not initialized.
+    NotInit => SASL_NOTINIT,
+    /// The server failed the mutual authentication step.
+    BadServ => SASL_BADSERV,
+    /// The mechanism does not support the requested feature.
+    WrongMech => SASL_WRONGMECH,
+    /// Authentication failure, e.g., an incorrect password.
+    BadAuth => SASL_BADAUTH,
+    /// Authorization failure.
+    NoAuthz => SASL_NOAUTHZ,
+    /// The mechanism is too weak for this user.
+    TooWeak => SASL_TOOWEAK,
+    /// Encryption is needed to use the mechanism.
+    Encrypt => SASL_ENCRYPT,
+    /// A one-time use of a plaintext password will enable the requested
+    /// mechanism for the user.
+    Trans => SASL_TRANS,
+    /// The passphrase expired and must be reset.
+    Expired => SASL_EXPIRED,
+    /// The account is disabled.
+    Disabled => SASL_DISABLED,
+    /// The user was not found.
+    NoUser => SASL_NOUSER,
+    /// Version mismatch with a plugin.
+    BadVers => SASL_BADVERS,
+    /// The remote authentication server is unavailable.
+    Unavail => SASL_UNAVAIL,
+    /// The user exists, but has no verifier for the mechanism.
+    NoVerify => SASL_NOVERIFY,
+    /// The passphrase is locked.
+    PwLock => SASL_PWLOCK,
+    /// The requested change was not needed.
+    NoChange => SASL_NOCHANGE,
+    /// The passphrase is too weak for the security policy.
+    WeakPass => SASL_WEAKPASS,
+    /// User-supplied passwords are not permitted.
+    NoUserPass => SASL_NOUSERPASS,
+    /// Setting the password requires the old password.
+    NeedOldPasswd => SASL_NEED_OLD_PASSWD,
+    /// A property constraint was violated.
+    ConstraintViolat => SASL_CONSTRAINT_VIOLAT,
+    /// Channel binding failure.
+    BadBinding => SASL_BADBINDING,
+}
+
+impl SaslError {
+    /// Constructs an error from a result code, using the generic description
+    /// of the code as the message.
+    pub fn from_code(code: c_int) -> SaslError {
+        SaslError::with_context(
+            code,
+            ErrorContext {
+                description: errstring(code),
+                detail: None,
+            },
+        )
+    }
+
+    /// Constructs an error from a result code, capturing the detailed error
+    /// message recorded on the connection.
+    ///
+    /// # Safety
+    ///
+    /// `conn` must be null or a valid connection.
+    pub unsafe fn from_conn(conn: *mut sasl_conn_t, code: c_int) -> SaslError {
+        let detail = if conn.is_null() {
+            None
+        } else {
+            util::copy_str(sasl_errdetail(conn))
+        };
+        SaslError::with_context(
+            code,
+            ErrorContext {
+                description: errstring(code),
+                detail,
+            },
+        )
+    }
+
+    /// Constructs an error indicating that a string argument contained an
+    /// interior nul byte.
+    pub(crate) fn nul() -> SaslError {
+        SaslError::bad_param("string contains an interior nul byte")
+    }
+
+    /// Constructs an error indicating that an argument was invalid for the
+    /// reason described by `detail`.
+    pub(crate) fn bad_param(detail: &str) -> SaslError {
+        SaslError::BadParam(ErrorContext {
+            description: errstring(SASL_BADPARAM),
+            detail: Some(detail.into()),
+        })
+    }
+
+    /// Constructs an error indicating that no acceptable mechanism was
+    /// found, for the reason described by `detail`.
+    pub(crate) fn no_mech(detail: &str) -> SaslError {
+        SaslError::NoMech(ErrorContext {
+            description: errstring(SASL_NOMECH),
+            detail: Some(detail.into()),
+        })
+    }
+
+    /// Constructs an error indicating that the peer or a mechanism violated
+    /// the protocol, in the way described by `detail`.
+    pub(crate) fn bad_prot(detail: &str) -> SaslError {
+        SaslError::BadProt(ErrorContext {
+            description: errstring(SASL_BADPROT),
+            detail: Some(detail.into()),
+        })
+    }
+
+    /// Constructs an error indicating that the linked libsasl2 does not
+    /// support an operation, for the reason described by `detail`.
+    pub(crate) fn bad_vers(detail: &str) -> SaslError {
+        SaslError::BadVers(ErrorContext {
+            description: errstring(SASL_BADVERS),
+            detail: Some(detail.into()),
+        })
+    }
+
+    /// Returns the generic description of the error.
+    ///
+    /// See [`ErrorContext::description`].
+    pub fn description(&self) -> &str {
+        self.context().description()
+    }
+
+    /// Returns the detailed error message captured from the connection, if
+    /// any.
+    ///
+    /// See [`ErrorContext::detail`].
+    pub fn detail(&self) -> Option<&str> {
+        self.context().detail()
+    }
+}
+
+impl fmt::Display for SaslError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.detail() {
+            Some(detail) => f.write_str(detail),
+            None => f.write_str(self.description()),
+        }
+    }
+}
+
+impl Error for SaslError {}
+
+impl From<SaslError> for io::Error {
+    fn from(err: SaslError) -> io::Error {
+        io::Error::other(err)
+    }
+}
+
+/// The non-error outcomes of a libsasl2 call.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Status {
+    /// The call succeeded (`SASL_OK`).
+    Ok,
+    /// Another step is needed in the exchange (`SASL_CONTINUE`).
+    Continue,
+    /// The application must answer prompts before the exchange can proceed
+    /// (`SASL_INTERACT`).
+    Interact,
+}
+
+/// Converts a libsasl2 result code into a `Result`.
+///
+/// `SASL_OK`, `SASL_CONTINUE`, and `SASL_INTERACT` are treated as successes.
+/// Any other code is converted into a [`SaslError`], capturing the detailed
+/// error message from `conn` if it is not null.
+///
+/// # Safety
+///
+/// `conn` must be null or a valid connection.
+pub unsafe fn check(conn: *mut sasl_conn_t, code: c_int) -> Result<Status, SaslError> {
+    match code {
+        SASL_OK => Ok(Status::Ok),
+        SASL_CONTINUE => Ok(Status::Continue),
+        SASL_INTERACT => Ok(Status::Interact),
+        _ => Err(SaslError::from_conn(conn, code)),
+    }
+}
+
+fn errstring(code: c_int) -> String {
+    let s = unsafe { util::copy_str(sasl_errstring(code, ptr::null(), ptr::null_mut())) };
+    s.unwrap_or_else(|| format!("unknown error {}", code))
+}
@@ -0,0 +1,175 @@
+// Copyright Materialize, Inc. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License in the LICENSE file at the
+// root of this repository, or online at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Interactive prompts.
+//!
+//! When a client mechanism needs information for which no callback is
+//! registered, like a password, the exchange pauses with
+//! [`Step::Interact`](crate::client::Step::Interact). The application answers
+//! the [`Prompts`] it is given and passes them back to
+//! [`SaslClient::interact`](crate::client::SaslClient::interact) to resume the
+//! exchange.
+
+use std::slice;
+use std::vec;
+
+use libc::c_ulong;
+use sasl2_sys::sasl::{sasl_interact_t, SASL_CB_LIST_END};
+
+use crate::util;
+
+/// A request for information from the user.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Prompt {
+    id: c_ulong,
+    challenge: Option<String>,
+    prompt: Option<String>,
+    default: Option<String>,
+    answer: Option<Vec<u8>>,
+}
+
+impl Prompt {
+    /// Returns the callback ID that identifies the requested information,
+    /// e.g., `SASL_CB_AUTHNAME`.
+    pub fn id(&self) -> c_ulong {
+        self.id
+    }
+
+    /// Returns the challenge presented by the server, if any.
+    pub fn challenge(&self) -> Option<&str> {
+        self.challenge.as_deref()
+    }
+
+    /// Returns a human-readable prompt, if any.
+    pub fn prompt(&self) -> Option<&str> {
+        self.prompt.as_deref()
+    }
+
+    /// Returns the default response, if any.
+    pub fn default(&self) -> Option<&str> {
+        self.default.as_deref()
+    }
+
+    /// Answers the prompt, replacing any previous answer.
+    ///
+    /// If a prompt is left unanswered, its default response is used, if it
+    /// has one. Otherwise the mechanism is told that no answer was provided,
+    /// which is only acceptable for optional information like the
+    /// authorization identity.
+    pub fn answer<A>(&mut self, answer: A)
+    where
+        A: Into<Vec<u8>>,
+    {
+        self.answer = Some(answer.into());
+    }
+
+    /// Returns the answer to the prompt, if it has been answered.
+    pub fn answered(&self) -> Option<&[u8]> {
+        self.answer.as_deref()
+    }
+
+    /// Returns the response that will be given to the mechanism.
+    pub(crate) fn response(&self) -> Option<&[u8]> {
+        self.answer
+            .as_deref()
+            .or_else(|| self.default.as_deref().map(str::as_bytes))
+    }
+}
+
+/// A set of prompts that must be answered before an exchange can proceed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Prompts {
+    prompts: Vec<Prompt>,
+    // Identifies the round of interaction that produced these prompts, so
+    // that stale prompts, or those of another client, are not applied to
+    // the paused exchange.
+    pub(crate) round: u64,
+}
+
+impl Prompts {
+    /// Copies the prompts out of a `SASL_CB_LIST_END`-terminated
+    /// `sasl_interact_t` array.
+    pub(crate) unsafe fn from_raw(mut raw: *const sasl_interact_t, round: u64) -> Prompts {
+        let mut prompts = vec![];
+        while !raw.is_null() && (*raw).id != SASL_CB_LIST_END {
+            prompts.push(Prompt {
+                id: (*raw).id,
+                challenge: util::copy_str((*raw).challenge),
+                prompt: util::copy_str((*raw).prompt),
+                default: util::copy_str((*raw).defresult),
+                answer: None,
+            });
+            raw = raw.add(1);
+        }
+        Prompts { prompts, round }
+    }
+
+    /// Returns the number of prompts.
+    pub fn len(&self) -> usize {
+        self.prompts.len()
+    }
+
+    /// Reports whether there are no prompts.
+    pub fn is_empty(&self) -> bool {
+        self.prompts.is_empty()
+    }
+
+    /// Returns the prompt for the callback ID `id`, if any.
+    pub fn get(&self, id: c_ulong) -> Option<&Prompt> {
+        self.prompts.iter().find(|p| p.id == id)
+    }
+
+    /// Returns the prompt for the callback ID `id` for answering, if any.
+    pub fn get_mut(&mut self, id: c_ulong) -> Option<&mut Prompt> {
+        self.prompts.iter_mut().find(|p| p.id == id)
+    }
+
+    /// Returns an iterator over the prompts.
+    pub fn iter(&self) -> slice::Iter<'_, Prompt> {
+        self.prompts.iter()
+    }
+
+    /// Returns an iterator that allows answering each prompt.
+    pub fn iter_mut(&mut self) -> slice::IterMut<'_, Prompt> {
+        self.prompts.iter_mut()
+    }
+}
+
+impl<'a> IntoIterator for &'a Prompts {
+    type Item = &'a Prompt;
+    type IntoIter = slice::Iter<'a, Prompt>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl<'a> IntoIterator for &'a mut Prompts {
+    type Item = &'a mut Prompt;
+    type IntoIter = slice::IterMut<'a, Prompt>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter_mut()
+    }
+}
+
+impl IntoIterator for Prompts {
+    type Item = Prompt;
+    type IntoIter = vec::IntoIter<Prompt>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.prompts.into_iter()
+    }
+}
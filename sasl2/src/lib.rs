@@ -0,0 +1,70 @@
+// Copyright Materialize, Inc. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License in the LICENSE file at the
+// root of this repository, or online at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Safe bindings to Cyrus SASL.
+//!
+//! This crate provides a safe, idiomatic interface to the [Cyrus SASL
+//! library][upstream], libsasl2, on top of the raw bindings in [sasl2-sys].
+//!
+//! Connections own their underlying `sasl_conn_t` and dispose of it when
+//! dropped. Any data that libsasl2 hands back to the application, like the
+//! output of a negotiation step, is copied into Rust-owned buffers before it is
+//! returned, so it remains valid regardless of subsequent calls into the
+//! library.
+//!
+//! The library is initialized when the first connection is created and torn
+//! down when the last one is dropped; see the [`context`] module to control
+//! this explicitly.
+//!
+//! # Build configuration
+//!
+//! The Cargo features of this crate mirror those of [sasl2-sys] and are simply
+//! forwarded to it. See the sasl2-sys documentation for details.
+//!
+//! Additionally, the `tokio` feature enables the `async_stream` module, which
+//! provides security layers for asynchronous streams, and the `log` and
+//! `tracing` features enable the `logging` module, which forwards libsasl2's
+//! log messages to the respective crates. The `serde` feature allows
+//! [`config::SaslConfig`] to be deserialized.
+//!
+//! [sasl2-sys]: https://docs.rs/sasl2-sys
+//! [upstream]: https://www.cyrusimap.org/sasl
+
+pub mod allocator;
+#[cfg(feature = "tokio")]
+pub mod async_stream;
+pub mod authz;
+pub mod auxprop;
+pub mod callbacks;
+pub mod canonuser;
+pub mod channel_binding;
+pub mod client;
+pub mod config;
+pub mod connection;
+pub mod context;
+pub mod error;
+pub mod interact;
+#[cfg(any(feature = "log", feature = "tracing"))]
+pub mod logging;
+pub mod mechanism;
+pub mod mutex;
+pub mod property;
+pub mod security;
+pub mod server;
+pub mod session;
+pub mod stream;
+pub mod userdb;
+
+mod util;
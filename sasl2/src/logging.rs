@@ -0,0 +1,136 @@
+// Copyright Materialize, Inc. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License in the LICENSE file at the
+// root of this repository, or online at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Log forwarding.
+//!
+//! [`LogForwarder`] produces `SASL_CB_LOG` handlers that forward the messages
+//! logged by libsasl2 and its plugins to the [`log`] crate (with the `log`
+//! feature) or the [`tracing`] crate (with the `tracing` feature).
+//!
+//! Messages are logged with the target `sasl2` and carry the `service`,
+//! `mechanism` and `remote` address of the connection they were logged on as
+//! fields. The levels map as follows:
+//!
+//! | libsasl2         | `log` / `tracing` |
+//! |------------------|-------------------|
+//! | `SASL_LOG_ERR`   | error             |
+//! | `SASL_LOG_FAIL`  | warn              |
+//! | `SASL_LOG_WARN`  | warn              |
+//! | `SASL_LOG_NOTE`  | info              |
+//! | `SASL_LOG_DEBUG` | debug             |
+//! | `SASL_LOG_TRACE` | trace             |
+//! | `SASL_LOG_PASS`  | trace             |
+//!
+//! `SASL_LOG_PASS` messages may contain passwords and are dropped unless
+//! [`LogForwarder::include_passwords`] is set.
+//!
+//! Register the handler on each connection, rather than globally, so that
+//! the fields are populated:
+//!
+//! ```no_run
+//! # #[cfg(feature = "tracing")] {
+//! use sasl2::callbacks::Callbacks;
+//! use sasl2::logging::LogForwarder;
+//! use sasl2::server::SaslServer;
+//!
+//! let server = SaslServer::builder("ldap")
+//!     .callbacks(Callbacks::new().log_with_source(LogForwarder::new().tracing()))
+//!     .build()?;
+//! # }
+//! # Ok::<(), sasl2::error::SaslError>(())
+//! ```
+//!
+//! [`log`]: https://docs.rs/log
+//! [`tracing`]: https://docs.rs/tracing
+
+use crate::callbacks::{LogLevel, LogSource};
+
+/// A builder for handlers that forward log messages to a logging crate.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct LogForwarder {
+    include_passwords: bool,
+}
+
+impl LogForwarder {
+    /// Creates a forwarder with the default settings.
+    pub fn new() -> LogForwarder {
+        LogForwarder::default()
+    }
+
+    /// Declares whether `SASL_LOG_PASS` messages, which may contain
+    /// passwords, are forwarded.
+    pub fn include_passwords(mut self, include: bool) -> LogForwarder {
+        self.include_passwords = include;
+        self
+    }
+
+    /// Returns a handler that forwards messages to the `log` crate, for use
+    /// with [`Callbacks::log_with_source`](crate::callbacks::Callbacks::log_with_source).
+    #[cfg(feature = "log")]
+    pub fn log(self) -> impl Fn(LogLevel, &str, &LogSource) + Send + Sync + 'static {
+        move |level, message, source| {
+            let level = match level {
+                LogLevel::Err => log::Level::Error,
+                LogLevel::Fail | LogLevel::Warn => log::Level::Warn,
+                LogLevel::Note => log::Level::Info,
+                LogLevel::Debug => log::Level::Debug,
+                LogLevel::Trace => log::Level::Trace,
+                LogLevel::Pass if self.include_passwords => log::Level::Trace,
+                LogLevel::Pass => return,
+            };
+            log::log!(
+                target: "sasl2",
+                level,
+                service = source.service(),
+                mechanism = source.mechanism(),
+                remote = source.remote_addr();
+                "{}",
+                message
+            );
+        }
+    }
+
+    /// Returns a handler that forwards messages to the `tracing` crate, for
+    /// use with [`Callbacks::log_with_source`](crate::callbacks::Callbacks::log_with_source).
+    #[cfg(feature = "tracing")]
+    pub fn tracing(self) -> impl Fn(LogLevel, &str, &LogSource) + Send + Sync + 'static {
+        // The level of a `tracing` event must be a constant.
+        macro_rules! event {
+            ($level:expr, $message:expr, $source:expr) => {
+                tracing::event!(
+                    target: "sasl2",
+                    $level,
+                    service = $source.service(),
+                    mechanism = $source.mechanism(),
+                    remote = $source.remote_addr(),
+                    "{}",
+                    $message
+                )
+            };
+        }
+
+        move |level, message, source| match level {
+            LogLevel::Err => event!(tracing::Level::ERROR, message, source),
+            LogLevel::Fail | LogLevel::Warn => event!(tracing::Level::WARN, message, source),
+            LogLevel::Note => event!(tracing::Level::INFO, message, source),
+            LogLevel::Debug => event!(tracing::Level::DEBUG, message, source),
+            LogLevel::Trace => event!(tracing::Level::TRACE, message, source),
+            LogLevel::Pass if self.include_passwords => {
+                event!(tracing::Level::TRACE, message, source)
+            }
+            LogLevel::Pass => (),
+        }
+    }
+}
@@ -0,0 +1,470 @@
+// Copyright Materialize, Inc. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License in the LICENSE file at the
+// root of this repository, or online at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Mechanism discovery.
+//!
+//! [`mechanisms`] describes every mechanism provided by the loaded plugins,
+//! on either side of the library. To list the mechanisms that a particular
+//! connection would negotiate, taking its security properties into account,
+//! see [`Connection::list_mechanisms`].
+//!
+//! ```no_run
+//! use sasl2::mechanism::{self, MechanismFeatures};
+//!
+//! for mech in mechanism::mechanisms()? {
+//!     if mech.server() && mech.features().contains(MechanismFeatures::CHANNEL_BINDING) {
+//!         println!("{} supports channel binding", mech.name());
+//!     }
+//! }
+//! # Ok::<(), sasl2::error::SaslError>(())
+//! ```
+//!
+//! Clients can choose among the mechanisms offered by a server with a
+//! [`MechanismPolicy`] rather than libsasl2's heuristics.
+//!
+//! [`Connection::list_mechanisms`]: crate::connection::Connection::list_mechanisms
+
+use std::fmt;
+use std::ptr;
+
+use bitflags::bitflags;
+use libc::{c_uint, c_void};
+use sasl2_sys::sasl::{sasl_global_listmech, sasl_ssf_t, SASL_OK};
+use sasl2_sys::saslplug::*;
+
+use crate::context::SaslContext;
+use crate::error::SaslError;
+use crate::security::SecurityFlags;
+use crate::util;
+
+bitflags! {
+    /// Features of a mechanism.
+    #[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+    pub struct MechanismFeatures: c_uint {
+        /// The client must know the server's fully-qualified domain name
+        /// (`SASL_FEAT_NEEDSERVERFQDN`).
+        const NEED_SERVER_FQDN = SASL_FEAT_NEEDSERVERFQDN;
+        /// The client sends the first message (`SASL_FEAT_WANT_CLIENT_FIRST`).
+        const WANT_CLIENT_FIRST = SASL_FEAT_WANT_CLIENT_FIRST;
+        /// The server sends the first message (`SASL_FEAT_SERVER_FIRST`).
+        const SERVER_FIRST = SASL_FEAT_SERVER_FIRST;
+        /// The mechanism supports proxy authorization
+        /// (`SASL_FEAT_ALLOWS_PROXY`).
+        const ALLOWS_PROXY = SASL_FEAT_ALLOWS_PROXY;
+        /// The mechanism does not use the user's password
+        /// (`SASL_FEAT_DONTUSE_USERPASSWD`).
+        const DONT_USE_USERPASSWD = SASL_FEAT_DONTUSE_USERPASSWD;
+        /// The security layer uses GSS-API framing (`SASL_FEAT_GSS_FRAMING`).
+        const GSS_FRAMING = SASL_FEAT_GSS_FRAMING;
+        /// The mechanism needs the service name (`SASL_FEAT_SERVICE`).
+        const SERVICE = SASL_FEAT_SERVICE;
+        /// The mechanism can retrieve the user's secret
+        /// (`SASL_FEAT_GETSECRET`).
+        const GET_SECRET = SASL_FEAT_GETSECRET;
+        /// The mechanism supports channel binding
+        /// (`SASL_FEAT_CHANNEL_BINDING`).
+        const CHANNEL_BINDING = SASL_FEAT_CHANNEL_BINDING;
+        /// The mechanism can be used with HTTP (`SASL_FEAT_SUPPORTS_HTTP`).
+        const SUPPORTS_HTTP = SASL_FEAT_SUPPORTS_HTTP;
+    }
+}
+
+/// A description of a mechanism.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MechanismInfo {
+    name: String,
+    plugin_name: Option<String>,
+    max_ssf: sasl_ssf_t,
+    security_flags: SecurityFlags,
+    features: MechanismFeatures,
+    client: bool,
+    server: bool,
+}
+
+impl MechanismInfo {
+    /// Returns the name of the mechanism, e.g., `"SCRAM-SHA-256"`.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns the name of the plugin that provides the mechanism.
+    pub fn plugin_name(&self) -> Option<&str> {
+        self.plugin_name.as_deref()
+    }
+
+    /// Returns the maximum security strength factor of the mechanism's
+    /// security layer, or zero if it has none.
+    pub fn max_ssf(&self) -> sasl_ssf_t {
+        self.max_ssf
+    }
+
+    /// Returns the security requirements that the mechanism meets.
+    pub fn security_flags(&self) -> SecurityFlags {
+        self.security_flags
+    }
+
+    /// Returns the features of the mechanism.
+    pub fn features(&self) -> MechanismFeatures {
+        self.features
+    }
+
+    /// Reports whether the mechanism is available to clients.
+    pub fn client(&self) -> bool {
+        self.client
+    }
+
+    /// Reports whether the mechanism is available to servers.
+    pub fn server(&self) -> bool {
+        self.server
+    }
+}
+
+/// Describes the mechanisms provided by the loaded client and server plugins
+/// (`sasl_client_plugin_info`, `sasl_server_plugin_info`).
+///
+/// Both sides of the library are initialized for the duration of the call,
+/// if necessary. Where the client and server plugins describe a mechanism
+/// differently, the server's description is returned.
+pub fn mechanisms() -> Result<Vec<MechanismInfo>, SaslError> {
+    let _client = SaslContext::acquire_client()?;
+    let _server = SaslContext::acquire_server()?;
+    let mut mechs: Vec<MechanismInfo> = vec![];
+    let res = unsafe {
+        sasl_client_plugin_info(
+            ptr::null(),
+            Some(client_info),
+            &mut mechs as *mut Vec<MechanismInfo> as *mut c_void,
+        )
+    };
+    if res != SASL_OK {
+        return Err(SaslError::from_code(res));
+    }
+    let mut server_mechs: Vec<MechanismInfo> = vec![];
+    let res = unsafe {
+        sasl_server_plugin_info(
+            ptr::null(),
+            Some(server_info),
+            &mut server_mechs as *mut Vec<MechanismInfo> as *mut c_void,
+        )
+    };
+    if res != SASL_OK {
+        return Err(SaslError::from_code(res));
+    }
+    for mech in server_mechs {
+        match mechs.iter_mut().find(|m| m.name == mech.name) {
+            Some(m) => {
+                *m = MechanismInfo {
+                    client: true,
+                    ..mech
+                }
+            }
+            None => mechs.push(mech),
+        }
+    }
+    Ok(mechs)
+}
+
+/// Returns the names of the mechanisms that servers may offer
+/// (`sasl_global_listmech`).
+///
+/// Unlike [`mechanisms`], this honors the `mech_list` option. The server side
+/// of the library is initialized for the duration of the call, if necessary.
+pub fn server_mechanism_names() -> Result<Vec<String>, SaslError> {
+    let _server = SaslContext::acquire_server()?;
+    let mut names = vec![];
+    unsafe {
+        let mut name = sasl_global_listmech();
+        while !name.is_null() && !(*name).is_null() {
+            names.extend(util::copy_str(*name));
+            name = name.add(1);
+        }
+    }
+    Ok(names)
+}
+
+/// Returns the names of the loaded auxiliary property plugins
+/// (`auxprop_plugin_info`).
+///
+/// The server side of the library is initialized for the duration of the
+/// call, if necessary.
+#[cfg(not(all(target_os = "macos", not(feature = "vendored"))))]
+pub fn auxprop_plugins() -> Result<Vec<String>, SaslError> {
+    let _server = SaslContext::acquire_server()?;
+    let mut names: Vec<String> = vec![];
+    let res = unsafe {
+        auxprop_plugin_info(
+            ptr::null(),
+            Some(auxprop_info),
+            &mut names as *mut Vec<String> as *mut c_void,
+        )
+    };
+    // libsasl2 reports that it is not initialized when no auxiliary property
+    // plugins are loaded.
+    if res != SASL_OK && res != sasl2_sys::sasl::SASL_NOTINIT {
+        return Err(SaslError::from_code(res));
+    }
+    Ok(names)
+}
+
+unsafe extern "C" fn client_info(
+    m: *mut client_sasl_mechanism_t,
+    stage: sasl_info_callback_stage_t,
+    rock: *mut c_void,
+) {
+    if stage != SASL_INFO_LIST_MECH || m.is_null() || (*m).plug.is_null() {
+        return;
+    }
+    let plug = &*(*m).plug;
+    let mechs = &mut *(rock as *mut Vec<MechanismInfo>);
+    mechs.extend(util::copy_str(plug.mech_name).map(|name| MechanismInfo {
+        name,
+        plugin_name: util::copy_str((*m).plugname),
+        max_ssf: plug.max_ssf,
+        security_flags: SecurityFlags::from_bits_retain(plug.security_flags),
+        features: MechanismFeatures::from_bits_retain(plug.features),
+        client: true,
+        server: false,
+    }));
+}
+
+unsafe extern "C" fn server_info(
+    m: *mut server_sasl_mechanism_t,
+    stage: sasl_info_callback_stage_t,
+    rock: *mut c_void,
+) {
+    if stage != SASL_INFO_LIST_MECH || m.is_null() || (*m).plug.is_null() {
+        return;
+    }
+    let plug = &*(*m).plug;
+    let mechs = &mut *(rock as *mut Vec<MechanismInfo>);
+    mechs.extend(util::copy_str(plug.mech_name).map(|name| MechanismInfo {
+        name,
+        plugin_name: util::copy_str((*m).plugname),
+        max_ssf: plug.max_ssf,
+        security_flags: SecurityFlags::from_bits_retain(plug.security_flags),
+        features: MechanismFeatures::from_bits_retain(plug.features),
+        client: false,
+        server: true,
+    }));
+}
+
+#[cfg(not(all(target_os = "macos", not(feature = "vendored"))))]
+unsafe extern "C" fn auxprop_info(
+    m: *mut sasl_auxprop_plug_t,
+    stage: sasl_info_callback_stage_t,
+    rock: *mut c_void,
+) {
+    if stage != SASL_INFO_LIST_MECH || m.is_null() {
+        return;
+    }
+    let names = &mut *(rock as *mut Vec<String>);
+    names.extend(util::copy_str((*m).name));
+}
+
+/// A client-side policy that chooses among the mechanisms offered by a
+/// server.
+///
+/// libsasl2 chooses a mechanism by its own heuristics, which favor the
+/// strongest security layer. A policy instead ranks the mechanisms that it
+/// allows explicitly, refuses all others, and records why each offered
+/// mechanism was not chosen. Install it with
+/// [`ClientBuilder::mechanism_policy`](crate::client::ClientBuilder::mechanism_policy).
+///
+/// ```
+/// use sasl2::mechanism::MechanismPolicy;
+///
+/// let policy = MechanismPolicy::new()
+///     .allow("SCRAM-SHA-256-PLUS")
+///     .allow("SCRAM-SHA-256")
+///     .allow("GSSAPI")
+///     .allow_over_tls("PLAIN");
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MechanismPolicy {
+    rules: Vec<Rule>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Rule {
+    name: String,
+    tls_only: bool,
+}
+
+impl MechanismPolicy {
+    /// Creates a policy that allows no mechanisms.
+    pub fn new() -> MechanismPolicy {
+        MechanismPolicy::default()
+    }
+
+    /// Allows the named mechanism, with lower preference than the mechanisms
+    /// allowed before it.
+    ///
+    /// Mechanisms whose names end in `-PLUS` are only chosen if channel
+    /// binding data is configured.
+    pub fn allow(self, name: &str) -> MechanismPolicy {
+        self.rule(name, false)
+    }
+
+    /// Like [`MechanismPolicy::allow`], but only allows the mechanism if the
+    /// transport is secured by TLS, as declared with
+    /// [`ClientBuilder::tls`](crate::client::ClientBuilder::tls).
+    pub fn allow_over_tls(self, name: &str) -> MechanismPolicy {
+        self.rule(name, true)
+    }
+
+    fn rule(mut self, name: &str, tls_only: bool) -> MechanismPolicy {
+        self.rules.retain(|r| !r.name.eq_ignore_ascii_case(name));
+        self.rules.push(Rule {
+            name: name.into(),
+            tls_only,
+        });
+        self
+    }
+
+    /// Chooses among the mechanisms in `offered`, a list separated by spaces
+    /// or commas.
+    ///
+    /// `available` lists the mechanisms that the client can negotiate.
+    pub(crate) fn select(
+        &self,
+        offered: &str,
+        available: &[String],
+        tls: bool,
+        channel_binding: bool,
+    ) -> Selection {
+        let mut candidates = vec![];
+        let mut skipped = vec![];
+        let offered = offered.split([' ', ',']).filter(|m| !m.is_empty());
+        for name in offered {
+            let rank = self
+                .rules
+                .iter()
+                .position(|r| r.name.eq_ignore_ascii_case(name));
+            // libsasl2 lists the -PLUS variant of a mechanism under the
+            // mechanism's own name.
+            let plus = name.to_ascii_uppercase().ends_with("-PLUS");
+            let base = if plus { &name[..name.len() - 5] } else { name };
+            let reason = match rank.map(|i| &self.rules[i]) {
+                None => Some(SkipReason::NotAllowed),
+                Some(rule) if rule.tls_only && !tls => Some(SkipReason::RequiresTls),
+                Some(_) if plus && !channel_binding => Some(SkipReason::RequiresChannelBinding),
+                Some(_) if !available.iter().any(|m| m.eq_ignore_ascii_case(base)) => {
+                    Some(SkipReason::Unavailable)
+                }
+                Some(_) => None,
+            };
+            match reason {
+                Some(reason) => skipped.push(SkippedMechanism {
+                    name: name.into(),
+                    reason,
+                }),
+                None => candidates.push((rank.unwrap(), name)),
+            }
+        }
+        candidates.sort_by_key(|(rank, _)| *rank);
+        let mut candidates = candidates.into_iter().map(|(_, name)| name);
+        let selected = candidates.next().map(String::from);
+        skipped.extend(candidates.map(|name| SkippedMechanism {
+            name: name.into(),
+            reason: SkipReason::Outranked,
+        }));
+        Selection { selected, skipped }
+    }
+}
+
+/// The reason that a [`MechanismPolicy`] did not choose an offered mechanism.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SkipReason {
+    /// The policy does not allow the mechanism.
+    NotAllowed,
+    /// The policy only allows the mechanism over TLS, and the transport is
+    /// not secured by TLS.
+    RequiresTls,
+    /// The mechanism uses channel binding, and no channel binding data is
+    /// configured.
+    RequiresChannelBinding,
+    /// The client cannot negotiate the mechanism, e.g., because no plugin
+    /// provides it or the security properties rule it out.
+    Unavailable,
+    /// The policy prefers the chosen mechanism.
+    Outranked,
+}
+
+impl fmt::Display for SkipReason {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            SkipReason::NotAllowed => "not allowed",
+            SkipReason::RequiresTls => "requires TLS",
+            SkipReason::RequiresChannelBinding => "requires channel binding",
+            SkipReason::Unavailable => "unavailable",
+            SkipReason::Outranked => "outranked",
+        })
+    }
+}
+
+/// An offered mechanism that a [`MechanismPolicy`] did not choose.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SkippedMechanism {
+    name: String,
+    reason: SkipReason,
+}
+
+impl SkippedMechanism {
+    /// Returns the name of the mechanism.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns the reason that the mechanism was not chosen.
+    pub fn reason(&self) -> SkipReason {
+        self.reason
+    }
+}
+
+/// The outcome of applying a [`MechanismPolicy`] to the mechanisms offered
+/// by a server.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Selection {
+    pub(crate) selected: Option<String>,
+    skipped: Vec<SkippedMechanism>,
+}
+
+impl Selection {
+    /// Returns the chosen mechanism, if any.
+    pub fn selected(&self) -> Option<&str> {
+        self.selected.as_deref()
+    }
+
+    /// Returns the offered mechanisms that were not chosen, in the order in
+    /// which they were offered, followed by those that were outranked in
+    /// order of preference.
+    pub fn skipped(&self) -> &[SkippedMechanism] {
+        &self.skipped
+    }
+}
+
+impl fmt::Display for Selection {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self.selected {
+            Some(mech) => write!(f, "selected {}", mech)?,
+            None => f.write_str("no acceptable mechanism")?,
+        }
+        for (i, skipped) in self.skipped.iter().enumerate() {
+            let sep = if i == 0 { "; skipped " } else { ", " };
+            write!(f, "{}{} ({})", sep, skipped.name, skipped.reason)?;
+        }
+        Ok(())
+    }
+}
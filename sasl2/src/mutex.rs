@@ -0,0 +1,125 @@
+// Copyright Materialize, Inc. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License in the LICENSE file at the
+// root of this repository, or online at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Internal locking.
+//!
+//! libsasl2 and its plugins protect shared state, like the list of loaded
+//! plugins and plugin-global caches, with mutexes that they obtain from a set
+//! of hooks. The default hooks do nothing, which makes concurrent use of
+//! libsasl2 from multiple threads unsafe. [`install`] replaces them with
+//! real mutexes built on the Rust standard library.
+//!
+//! Like the allocation functions, the hooks must be installed before the
+//! library is first initialized, i.e., before any connection or
+//! [`SaslContext`](crate::context::SaslContext) is created. Once installed,
+//! connections may be created and used concurrently from any number of
+//! threads.
+//!
+//! ```no_run
+//! sasl2::mutex::install()?;
+//! # Ok::<(), sasl2::error::SaslError>(())
+//! ```
+
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Condvar, Mutex};
+
+use libc::{c_int, c_void};
+use sasl2_sys::sasl::{sasl_set_mutex, SASL_FAIL, SASL_OK};
+
+use crate::context;
+use crate::error::SaslError;
+
+static INSTALLED: AtomicBool = AtomicBool::new(false);
+static LOCKS: AtomicUsize = AtomicUsize::new(0);
+static UNLOCKS: AtomicUsize = AtomicUsize::new(0);
+
+/// Installs mutex hooks backed by Rust mutexes (`sasl_set_mutex`).
+///
+/// Returns a [`SaslError::BadParam`] error if the hooks have already been
+/// installed or the library has already been initialized.
+pub fn install() -> Result<(), SaslError> {
+    context::configure(|| {
+        if INSTALLED.swap(true, Ordering::SeqCst) {
+            return Err(SaslError::bad_param("mutexes already installed"));
+        }
+        unsafe {
+            sasl_set_mutex(
+                Some(sasl_mutex_alloc),
+                Some(sasl_mutex_lock),
+                Some(sasl_mutex_unlock),
+                Some(sasl_mutex_free),
+            );
+        }
+        Ok(())
+    })
+}
+
+/// Returns the number of times that the hooks have locked and unlocked a
+/// mutex, for tests.
+#[doc(hidden)]
+pub fn lock_counts() -> (usize, usize) {
+    (LOCKS.load(Ordering::SeqCst), UNLOCKS.load(Ordering::SeqCst))
+}
+
+/// A mutex that, unlike [`Mutex`] itself, can be locked and unlocked by
+/// separate calls, as libsasl2 requires.
+#[derive(Default)]
+struct RawMutex {
+    locked: Mutex<bool>,
+    unlocked: Condvar,
+}
+
+unsafe extern "C" fn sasl_mutex_alloc() -> *mut c_void {
+    Box::into_raw(Box::<RawMutex>::default()) as *mut c_void
+}
+
+unsafe extern "C" fn sasl_mutex_lock(mutex: *mut c_void) -> c_int {
+    if mutex.is_null() {
+        return SASL_FAIL;
+    }
+    let mutex = &*(mutex as *const RawMutex);
+    let mut locked = mutex.locked.lock().unwrap_or_else(|e| e.into_inner());
+    while *locked {
+        locked = mutex
+            .unlocked
+            .wait(locked)
+            .unwrap_or_else(|e| e.into_inner());
+    }
+    *locked = true;
+    LOCKS.fetch_add(1, Ordering::Relaxed);
+    SASL_OK
+}
+
+unsafe extern "C" fn sasl_mutex_unlock(mutex: *mut c_void) -> c_int {
+    if mutex.is_null() {
+        return SASL_FAIL;
+    }
+    let mutex = &*(mutex as *const RawMutex);
+    let mut locked = mutex.locked.lock().unwrap_or_else(|e| e.into_inner());
+    if !*locked {
+        return SASL_FAIL;
+    }
+    *locked = false;
+    UNLOCKS.fetch_add(1, Ordering::Relaxed);
+    drop(locked);
+    mutex.unlocked.notify_one();
+    SASL_OK
+}
+
+unsafe extern "C" fn sasl_mutex_free(mutex: *mut c_void) {
+    if !mutex.is_null() {
+        drop(Box::from_raw(mutex as *mut RawMutex));
+    }
+}
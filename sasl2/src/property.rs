@@ -0,0 +1,559 @@
+// Copyright Materialize, Inc. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License in the LICENSE file at the
+// root of this repository, or online at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Connection properties.
+//!
+//! Each type in this module identifies one of the `SASL_*` property numbers
+//! accepted by `sasl_getprop` and `sasl_setprop`, and determines the type of
+//! the property's value. Properties are read with
+//! [`Connection::get_property`] and written with
+//! [`Connection::set_property`].
+//!
+//! ```no_run
+//! use sasl2::connection::Connection;
+//! use sasl2::property::{AuthExternal, Ssf, SsfExternal};
+//! # fn f(conn: &mut sasl2::server::SaslServer) -> Result<(), sasl2::error::SaslError> {
+//! conn.set_property::<AuthExternal>("alice")?;
+//! conn.set_property::<SsfExternal>(&256)?;
+//! let ssf = conn.get_property::<Ssf>()?;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! [`Connection::get_property`]: crate::connection::Connection::get_property
+//! [`Connection::set_property`]: crate::connection::Connection::set_property
+
+use std::any::Any;
+use std::convert::TryFrom;
+use std::ffi::CString;
+use std::fmt;
+use std::net::SocketAddr;
+
+use libc::{c_char, c_uint, c_ulong, c_void};
+use sasl2_sys::sasl::*;
+
+use crate::channel_binding;
+use crate::error::SaslError;
+use crate::security::SecurityProperties;
+use crate::util;
+
+mod private {
+    pub trait Sealed {}
+}
+
+/// A connection property.
+///
+/// This trait is sealed, and is implemented only by the types in this
+/// module.
+pub trait Property: private::Sealed {
+    /// The `SASL_*` property number.
+    const ID: c_uint;
+}
+
+/// A property that can be read with `sasl_getprop`.
+pub trait GetProperty: Property {
+    /// The type of the property's value.
+    type Value;
+
+    /// Converts the pointer returned by `sasl_getprop` into a value.
+    ///
+    /// # Safety
+    ///
+    /// `value` must be the non-null pointer returned by `sasl_getprop` for
+    /// this property.
+    #[doc(hidden)]
+    unsafe fn from_raw(value: *const c_void) -> Self::Value;
+}
+
+/// A property that can be written with `sasl_setprop`.
+pub trait SetProperty: Property {
+    /// The type of the property's value.
+    type Value: ?Sized;
+
+    /// Converts `value` into the pointer to pass to `sasl_setprop`.
+    #[doc(hidden)]
+    fn to_raw(value: &Self::Value) -> Result<RawValue, SaslError>;
+}
+
+/// The raw form of a property value, along with the data it points to.
+#[doc(hidden)]
+pub struct RawValue {
+    pub(crate) ptr: *const c_void,
+    // Set if libsasl2 retains `ptr` rather than copying what it points to,
+    // in which case the connection must keep `owner` alive.
+    pub(crate) retained: bool,
+    _owner: Box<dyn Any + Send>,
+}
+
+impl RawValue {
+    /// Constructs a raw value that libsasl2 copies.
+    ///
+    /// `ptr` must remain valid until the call to `sasl_setprop`, either
+    /// because it points into `owner` or because it borrows from the value
+    /// passed to [`Connection::set_property`].
+    ///
+    /// [`Connection::set_property`]: crate::connection::Connection::set_property
+    pub(crate) fn copied<T>(ptr: *const c_void, owner: T) -> RawValue
+    where
+        T: Any + Send,
+    {
+        RawValue {
+            ptr,
+            retained: false,
+            _owner: Box::new(owner),
+        }
+    }
+
+    /// Constructs a raw value that libsasl2 retains, and which therefore must
+    /// point into `owner`.
+    pub(crate) fn retained<T>(ptr: *const c_void, owner: T) -> RawValue
+    where
+        T: Any + Send,
+    {
+        RawValue {
+            ptr,
+            retained: true,
+            _owner: Box::new(owner),
+        }
+    }
+}
+
+impl fmt::Debug for RawValue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("RawValue")
+            .field("ptr", &self.ptr)
+            .field("retained", &self.retained)
+            .finish()
+    }
+}
+
+macro_rules! property {
+    ($(#[$meta:meta])* $name:ident => $id:ident) => {
+        $(#[$meta])*
+        #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+        pub enum $name {}
+
+        impl private::Sealed for $name {}
+
+        impl Property for $name {
+            const ID: c_uint = $id;
+        }
+    };
+}
+
+macro_rules! get_copy {
+    ($name:ident, $ty:ty) => {
+        impl GetProperty for $name {
+            type Value = $ty;
+
+            unsafe fn from_raw(value: *const c_void) -> $ty {
+                *(value as *const $ty)
+            }
+        }
+    };
+}
+
+macro_rules! get_ptr {
+    ($name:ident, $ty:ty) => {
+        impl GetProperty for $name {
+            type Value = $ty;
+
+            unsafe fn from_raw(value: *const c_void) -> $ty {
+                value as $ty
+            }
+        }
+    };
+}
+
+macro_rules! get_str {
+    ($name:ident) => {
+        impl GetProperty for $name {
+            type Value = String;
+
+            unsafe fn from_raw(value: *const c_void) -> String {
+                util::copy_str(value as *const c_char).unwrap_or_default()
+            }
+        }
+    };
+}
+
+macro_rules! set_copy {
+    ($name:ident, $ty:ty) => {
+        impl SetProperty for $name {
+            type Value = $ty;
+
+            fn to_raw(value: &$ty) -> Result<RawValue, SaslError> {
+                Ok(RawValue::copied(value as *const $ty as *const c_void, ()))
+            }
+        }
+    };
+}
+
+macro_rules! set_str {
+    ($name:ident) => {
+        impl SetProperty for $name {
+            type Value = str;
+
+            fn to_raw(value: &str) -> Result<RawValue, SaslError> {
+                let value = util::to_cstring(value)?;
+                Ok(RawValue::copied(value.as_ptr() as *const c_void, value))
+            }
+        }
+    };
+}
+
+macro_rules! set_addr {
+    ($name:ident) => {
+        impl SetProperty for $name {
+            type Value = SocketAddr;
+
+            fn to_raw(value: &SocketAddr) -> Result<RawValue, SaslError> {
+                let value = util::format_addr(*value);
+                Ok(RawValue::copied(value.as_ptr() as *const c_void, value))
+            }
+        }
+    };
+}
+
+property! {
+    /// The authorization identity of the authenticated user
+    /// (`SASL_USERNAME`).
+    Username => SASL_USERNAME
+}
+get_str!(Username);
+
+property! {
+    /// The security strength factor of the negotiated security layer
+    /// (`SASL_SSF`).
+    Ssf => SASL_SSF
+}
+get_copy!(Ssf, sasl_ssf_t);
+
+property! {
+    /// The maximum size of the buffers that may be passed to `sasl_encode`
+    /// (`SASL_MAXOUTBUF`).
+    MaxOutBuf => SASL_MAXOUTBUF
+}
+get_copy!(MaxOutBuf, c_uint);
+
+property! {
+    /// The realm that users belong to when they do not specify one
+    /// (`SASL_DEFUSERREALM`). Server connections only.
+    DefUserRealm => SASL_DEFUSERREALM
+}
+get_str!(DefUserRealm);
+set_str!(DefUserRealm);
+
+property! {
+    /// The context of the `SASL_CB_GETOPT` callback (`SASL_GETOPTCTX`).
+    GetOptCtx => SASL_GETOPTCTX
+}
+get_ptr!(GetOptCtx, *mut c_void);
+
+property! {
+    /// The connection's callback list (`SASL_CALLBACK`).
+    Callback => SASL_CALLBACK
+}
+get_ptr!(Callback, *const sasl_callback_t);
+
+property! {
+    /// The local address of the underlying transport, in `ip;port` form
+    /// (`SASL_IPLOCALPORT`).
+    IpLocalPort => SASL_IPLOCALPORT
+}
+get_str!(IpLocalPort);
+set_addr!(IpLocalPort);
+
+property! {
+    /// The remote address of the underlying transport, in `ip;port` form
+    /// (`SASL_IPREMOTEPORT`).
+    IpRemotePort => SASL_IPREMOTEPORT
+}
+get_str!(IpRemotePort);
+set_addr!(IpRemotePort);
+
+property! {
+    /// The most recent error message set by a plugin (`SASL_PLUGERR`).
+    PlugErr => SASL_PLUGERR
+}
+get_str!(PlugErr);
+
+property! {
+    /// The credentials delegated by the client, if any
+    /// (`SASL_DELEGATEDCREDS`). For GSSAPI, this is a `gss_cred_id_t`.
+    DelegatedCreds => SASL_DELEGATEDCREDS
+}
+get_ptr!(DelegatedCreds, *const c_void);
+
+property! {
+    /// The name of the service (`SASL_SERVICE`).
+    Service => SASL_SERVICE
+}
+get_str!(Service);
+
+property! {
+    /// The fully-qualified domain name of the server (`SASL_SERVERFQDN`).
+    ServerFqdn => SASL_SERVERFQDN
+}
+get_str!(ServerFqdn);
+
+property! {
+    /// The name of the plugin that provides the negotiated mechanism
+    /// (`SASL_AUTHSOURCE`).
+    AuthSource => SASL_AUTHSOURCE
+}
+get_str!(AuthSource);
+
+property! {
+    /// The name of the negotiated mechanism (`SASL_MECHNAME`).
+    MechName => SASL_MECHNAME
+}
+get_str!(MechName);
+
+property! {
+    /// The authentication identity of the authenticated user
+    /// (`SASL_AUTHUSER`).
+    AuthUser => SASL_AUTHUSER
+}
+get_str!(AuthUser);
+
+property! {
+    /// The name of the application (`SASL_APPNAME`). Server connections
+    /// only.
+    AppName => SASL_APPNAME
+}
+get_str!(AppName);
+set_str!(AppName);
+
+property! {
+    /// The GSSAPI credentials to use for authentication (`SASL_GSS_CREDS`).
+    /// This is a `gss_cred_id_t`.
+    GssCreds => SASL_GSS_CREDS
+}
+get_ptr!(GssCreds, *const c_void);
+
+impl SetProperty for GssCreds {
+    type Value = GssCredentials;
+
+    fn to_raw(value: &GssCredentials) -> Result<RawValue, SaslError> {
+        // libsasl2 retains the handle, which the caller keeps valid.
+        Ok(RawValue::retained(value.ptr, *value))
+    }
+}
+
+/// A handle to GSSAPI credentials, for the [`GssCreds`] property.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct GssCredentials {
+    ptr: *const c_void,
+}
+
+impl GssCredentials {
+    /// Wraps a `gss_cred_id_t`.
+    ///
+    /// # Safety
+    ///
+    /// `cred` must be a valid credential handle, and must remain valid until
+    /// the property is next written or the connection is dropped. The
+    /// connection does not release it.
+    pub unsafe fn from_raw(cred: *const c_void) -> GssCredentials {
+        GssCredentials { ptr: cred }
+    }
+
+    /// Returns the `gss_cred_id_t`.
+    pub fn as_ptr(&self) -> *const c_void {
+        self.ptr
+    }
+}
+
+// SAFETY: GSSAPI credential handles are not tied to the thread that acquired
+// them.
+unsafe impl Send for GssCredentials {}
+
+property! {
+    /// The GSSAPI name of the peer (`SASL_GSS_PEER_NAME`).
+    GssPeerName => SASL_GSS_PEER_NAME
+}
+get_str!(GssPeerName);
+
+property! {
+    /// The GSSAPI name of the local principal (`SASL_GSS_LOCAL_NAME`).
+    GssLocalName => SASL_GSS_LOCAL_NAME
+}
+get_str!(GssLocalName);
+
+property! {
+    /// The channel binding data of the underlying secure channel
+    /// (`SASL_CHANNEL_BINDING`). Write only.
+    ChannelBinding => SASL_CHANNEL_BINDING
+}
+
+impl SetProperty for ChannelBinding {
+    type Value = channel_binding::ChannelBinding;
+
+    fn to_raw(value: &channel_binding::ChannelBinding) -> Result<RawValue, SaslError> {
+        // libsasl2 retains the pointer to the binding.
+        let raw = value.to_raw()?;
+        Ok(RawValue::retained(
+            &raw.binding as *const _ as *const c_void,
+            raw,
+        ))
+    }
+}
+
+property! {
+    /// The HTTP request, for HTTP-based mechanisms (`SASL_HTTP_REQUEST`).
+    HttpRequest => SASL_HTTP_REQUEST
+}
+get_ptr!(HttpRequest, *const sasl_http_request_t);
+
+impl SetProperty for HttpRequest {
+    type Value = HttpRequestData;
+
+    fn to_raw(value: &HttpRequestData) -> Result<RawValue, SaslError> {
+        // libsasl2 retains the pointer to the request.
+        let raw = value.to_raw()?;
+        Ok(RawValue::retained(
+            &raw.request as *const _ as *const c_void,
+            raw,
+        ))
+    }
+}
+
+/// An HTTP request, for the [`HttpRequest`] property.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HttpRequestData {
+    method: String,
+    uri: String,
+    entity: Vec<u8>,
+    non_persistent: bool,
+}
+
+impl HttpRequestData {
+    /// Constructs a request with the given method (e.g., `"GET"`), URI, and
+    /// body, over a persistent connection.
+    pub fn new<D>(method: &str, uri: &str, entity: D) -> HttpRequestData
+    where
+        D: Into<Vec<u8>>,
+    {
+        HttpRequestData {
+            method: method.into(),
+            uri: uri.into(),
+            entity: entity.into(),
+            non_persistent: false,
+        }
+    }
+
+    /// Declares whether the underlying HTTP connection is closed after the
+    /// response.
+    pub fn non_persistent(mut self, non_persistent: bool) -> HttpRequestData {
+        self.non_persistent = non_persistent;
+        self
+    }
+
+    /// Returns the method of the request.
+    pub fn method(&self) -> &str {
+        &self.method
+    }
+
+    /// Returns the URI of the request.
+    pub fn uri(&self) -> &str {
+        &self.uri
+    }
+
+    /// Returns the body of the request.
+    pub fn entity(&self) -> &[u8] {
+        &self.entity
+    }
+
+    /// Reports whether the underlying HTTP connection is closed after the
+    /// response.
+    pub fn is_non_persistent(&self) -> bool {
+        self.non_persistent
+    }
+
+    fn to_raw(&self) -> Result<Box<RawHttpRequest>, SaslError> {
+        let method = util::to_cstring(&self.method)?;
+        let uri = util::to_cstring(&self.uri)?;
+        let entity = self.entity.clone().into_boxed_slice();
+        let elen = c_ulong::try_from(entity.len())
+            .map_err(|_| SaslError::bad_param("HTTP request body too long"))?;
+        Ok(Box::new(RawHttpRequest {
+            request: sasl_http_request_t {
+                method: method.as_ptr(),
+                uri: uri.as_ptr(),
+                entity: entity.as_ptr(),
+                elen,
+                non_persist: c_uint::from(self.non_persistent),
+            },
+            _method: method,
+            _uri: uri,
+            _entity: entity,
+        }))
+    }
+}
+
+/// A `sasl_http_request_t` along with the data it points to.
+struct RawHttpRequest {
+    request: sasl_http_request_t,
+    _method: CString,
+    _uri: CString,
+    _entity: Box<[u8]>,
+}
+
+// SAFETY: the pointers refer only to data owned by the struct itself.
+unsafe impl Send for RawHttpRequest {}
+
+property! {
+    /// The security strength factor of the external security layer, like
+    /// TLS (`SASL_SSF_EXTERNAL`).
+    SsfExternal => SASL_SSF_EXTERNAL
+}
+get_copy!(SsfExternal, sasl_ssf_t);
+set_copy!(SsfExternal, sasl_ssf_t);
+
+property! {
+    /// The security properties of the connection (`SASL_SEC_PROPS`).
+    SecProps => SASL_SEC_PROPS
+}
+
+impl GetProperty for SecProps {
+    type Value = SecurityProperties;
+
+    unsafe fn from_raw(value: *const c_void) -> SecurityProperties {
+        SecurityProperties::from_raw(&*(value as *const sasl_security_properties))
+    }
+}
+
+impl SetProperty for SecProps {
+    type Value = SecurityProperties;
+
+    fn to_raw(value: &SecurityProperties) -> Result<RawValue, SaslError> {
+        // libsasl2 copies the struct, but not the names and values it points
+        // to.
+        let raw = value.to_raw()?;
+        Ok(RawValue::retained(
+            &raw.props as *const _ as *const c_void,
+            raw,
+        ))
+    }
+}
+
+property! {
+    /// The identity authenticated by the external security layer, like TLS
+    /// (`SASL_AUTH_EXTERNAL`).
+    AuthExternal => SASL_AUTH_EXTERNAL
+}
+get_str!(AuthExternal);
+set_str!(AuthExternal);
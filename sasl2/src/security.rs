@@ -0,0 +1,251 @@
+// Copyright Materialize, Inc. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License in the LICENSE file at the
+// root of this repository, or online at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Security properties.
+//!
+//! Security properties constrain the mechanisms that a connection will
+//! negotiate, e.g., by ruling out mechanisms that send passwords in the
+//! clear, and bound the strength of the security layer. They are applied with
+//! [`ClientBuilder::security_properties`] and
+//! [`ServerBuilder::security_properties`], or at any time before the exchange
+//! starts via the [`SecProps`] property.
+//!
+//! ```
+//! use sasl2::security::{SecurityFlags, SecurityProperties};
+//!
+//! let props = SecurityProperties::builder()
+//!     .max_ssf(256)
+//!     .max_buf_size(65536)
+//!     .flags(SecurityFlags::NO_PLAINTEXT | SecurityFlags::NO_ANONYMOUS)
+//!     .build();
+//! assert!(props.flags().contains(SecurityFlags::NO_PLAINTEXT));
+//! ```
+//!
+//! [`ClientBuilder::security_properties`]: crate::client::ClientBuilder::security_properties
+//! [`ServerBuilder::security_properties`]: crate::server::ServerBuilder::security_properties
+//! [`SecProps`]: crate::property::SecProps
+
+use std::ffi::CString;
+use std::ptr;
+
+use bitflags::bitflags;
+use libc::{c_char, c_uint};
+use sasl2_sys::sasl::*;
+
+use crate::error::SaslError;
+use crate::util;
+
+bitflags! {
+    /// Requirements that a mechanism must meet to be negotiated.
+    #[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+    pub struct SecurityFlags: c_uint {
+        /// Reject mechanisms that are susceptible to simple passive attacks,
+        /// like PLAIN (`SASL_SEC_NOPLAINTEXT`).
+        const NO_PLAINTEXT = SASL_SEC_NOPLAINTEXT;
+        /// Reject mechanisms that are susceptible to active, non-dictionary
+        /// attacks (`SASL_SEC_NOACTIVE`).
+        const NO_ACTIVE = SASL_SEC_NOACTIVE;
+        /// Reject mechanisms that are susceptible to passive dictionary
+        /// attacks (`SASL_SEC_NODICTIONARY`).
+        const NO_DICTIONARY = SASL_SEC_NODICTIONARY;
+        /// Require mechanisms that provide forward secrecy
+        /// (`SASL_SEC_FORWARD_SECRECY`).
+        const FORWARD_SECRECY = SASL_SEC_FORWARD_SECRECY;
+        /// Reject mechanisms that allow anonymous login, like ANONYMOUS
+        /// (`SASL_SEC_NOANONYMOUS`).
+        const NO_ANONYMOUS = SASL_SEC_NOANONYMOUS;
+        /// Require mechanisms that pass client credentials to the server
+        /// (`SASL_SEC_PASS_CREDENTIALS`).
+        const PASS_CREDENTIALS = SASL_SEC_PASS_CREDENTIALS;
+        /// Require mechanisms that authenticate the server to the client
+        /// (`SASL_SEC_MUTUAL_AUTH`).
+        const MUTUAL_AUTH = SASL_SEC_MUTUAL_AUTH;
+    }
+}
+
+/// The security properties of a connection.
+///
+/// The default properties, which libsasl2 also uses for new connections,
+/// impose no requirements but also disable security layers, as the maximum
+/// buffer size is zero.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SecurityProperties {
+    min_ssf: sasl_ssf_t,
+    max_ssf: sasl_ssf_t,
+    max_buf_size: c_uint,
+    flags: SecurityFlags,
+    properties: Vec<(String, String)>,
+}
+
+impl SecurityProperties {
+    /// Returns a builder for a `SecurityProperties`.
+    pub fn builder() -> SecurityPropertiesBuilder {
+        SecurityPropertiesBuilder {
+            props: SecurityProperties::default(),
+        }
+    }
+
+    /// Returns the minimum acceptable security strength factor.
+    pub fn min_ssf(&self) -> sasl_ssf_t {
+        self.min_ssf
+    }
+
+    /// Returns the maximum acceptable security strength factor.
+    pub fn max_ssf(&self) -> sasl_ssf_t {
+        self.max_ssf
+    }
+
+    /// Returns the size of the largest buffer that the security layer may
+    /// receive.
+    pub fn max_buf_size(&self) -> c_uint {
+        self.max_buf_size
+    }
+
+    /// Returns the requirements that a mechanism must meet.
+    pub fn flags(&self) -> SecurityFlags {
+        self.flags
+    }
+
+    /// Returns the additional name-value properties.
+    pub fn properties(&self) -> &[(String, String)] {
+        &self.properties
+    }
+
+    /// Copies the properties out of a `sasl_security_properties`.
+    pub(crate) unsafe fn from_raw(raw: &sasl_security_properties) -> SecurityProperties {
+        let mut properties = vec![];
+        if !raw.property_names.is_null() && !raw.property_values.is_null() {
+            let mut i = 0;
+            while !(*raw.property_names.add(i)).is_null() {
+                let name = util::copy_str(*raw.property_names.add(i));
+                let value = util::copy_str(*raw.property_values.add(i));
+                properties.push((name.unwrap_or_default(), value.unwrap_or_default()));
+                i += 1;
+            }
+        }
+        SecurityProperties {
+            min_ssf: raw.min_ssf,
+            max_ssf: raw.max_ssf,
+            max_buf_size: raw.maxbufsize,
+            flags: SecurityFlags::from_bits_retain(raw.security_flags),
+            properties,
+        }
+    }
+
+    /// Converts the properties into a `sasl_security_properties`.
+    pub(crate) fn to_raw(&self) -> Result<Box<RawSecurityProperties>, SaslError> {
+        let mut strings = vec![];
+        let mut names = vec![];
+        let mut values = vec![];
+        for (name, value) in &self.properties {
+            let name = util::to_cstring(name)?;
+            let value = util::to_cstring(value)?;
+            names.push(name.as_ptr());
+            values.push(value.as_ptr());
+            strings.push(name);
+            strings.push(value);
+        }
+        let (names, values) = if names.is_empty() {
+            (None, None)
+        } else {
+            names.push(ptr::null());
+            values.push(ptr::null());
+            (Some(names), Some(values))
+        };
+        let mut raw = Box::new(RawSecurityProperties {
+            props: sasl_security_properties {
+                min_ssf: self.min_ssf,
+                max_ssf: self.max_ssf,
+                maxbufsize: self.max_buf_size,
+                security_flags: self.flags.bits(),
+                property_names: ptr::null_mut(),
+                property_values: ptr::null_mut(),
+            },
+            names,
+            values,
+            _strings: strings,
+        });
+        if let Some(names) = &mut raw.names {
+            raw.props.property_names = names.as_mut_ptr();
+        }
+        if let Some(values) = &mut raw.values {
+            raw.props.property_values = values.as_mut_ptr();
+        }
+        Ok(raw)
+    }
+}
+
+/// A builder for [`SecurityProperties`].
+#[derive(Debug, Clone)]
+pub struct SecurityPropertiesBuilder {
+    props: SecurityProperties,
+}
+
+impl SecurityPropertiesBuilder {
+    /// Sets the minimum acceptable security strength factor.
+    ///
+    /// Roughly, this is the number of bits of encryption that the security
+    /// layer must provide: 0 means no protection, and 1 means integrity
+    /// protection only.
+    pub fn min_ssf(mut self, ssf: sasl_ssf_t) -> SecurityPropertiesBuilder {
+        self.props.min_ssf = ssf;
+        self
+    }
+
+    /// Sets the maximum acceptable security strength factor.
+    pub fn max_ssf(mut self, ssf: sasl_ssf_t) -> SecurityPropertiesBuilder {
+        self.props.max_ssf = ssf;
+        self
+    }
+
+    /// Sets the size of the largest buffer that the security layer may
+    /// receive.
+    ///
+    /// Zero disables security layers, in which case the minimum security
+    /// strength factor must also be zero.
+    pub fn max_buf_size(mut self, size: c_uint) -> SecurityPropertiesBuilder {
+        self.props.max_buf_size = size;
+        self
+    }
+
+    /// Sets the requirements that a mechanism must meet, replacing any
+    /// previously set.
+    pub fn flags(mut self, flags: SecurityFlags) -> SecurityPropertiesBuilder {
+        self.props.flags = flags;
+        self
+    }
+
+    /// Adds a name-value property for mechanisms to consult.
+    pub fn property(mut self, name: &str, value: &str) -> SecurityPropertiesBuilder {
+        self.props.properties.push((name.into(), value.into()));
+        self
+    }
+
+    /// Builds the security properties.
+    pub fn build(self) -> SecurityProperties {
+        self.props
+    }
+}
+
+/// A `sasl_security_properties` along with the strings it points to.
+pub(crate) struct RawSecurityProperties {
+    pub(crate) props: sasl_security_properties,
+    names: Option<Vec<*const c_char>>,
+    values: Option<Vec<*const c_char>>,
+    _strings: Vec<CString>,
+}
+
+// SAFETY: the pointers refer only to data owned by the struct itself.
+unsafe impl Send for RawSecurityProperties {}
@@ -0,0 +1,529 @@
+// Copyright Materialize, Inc. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License in the LICENSE file at the
+// root of this repository, or online at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Server connections.
+
+use std::collections::HashMap;
+use std::ffi::CString;
+use std::net::SocketAddr;
+use std::ptr;
+
+use libc::{c_char, c_int, c_uint};
+use sasl2_sys::sasl::{
+    sasl_auxprop_getctx, sasl_auxprop_request, sasl_callback_t, sasl_checkpass, sasl_conn_t,
+    sasl_dispose, sasl_server_new, sasl_server_start, sasl_server_step, sasl_setpass, sasl_ssf_t,
+    sasl_user_exists, SASL_AUTHUSER, SASL_MECHNAME, SASL_NEED_PROXY, SASL_OK, SASL_SSF,
+    SASL_SUCCESS_DATA, SASL_USERNAME,
+};
+use sasl2_sys::saslutil::sasl_erasebuffer;
+
+use crate::auxprop::AuxProps;
+use crate::callbacks::Callbacks;
+use crate::channel_binding::ChannelBinding;
+use crate::connection::Connection;
+use crate::context::SaslContext;
+use crate::error::{self, SaslError, Status};
+use crate::property::{self, RawValue, SecProps};
+use crate::security::SecurityProperties;
+use crate::userdb::SetPasswordFlags;
+use crate::util;
+
+/// The outcome of a single step of an authentication exchange.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Step {
+    /// The exchange is not yet complete. The contained data must be sent to
+    /// the peer, and the peer's response supplied to the next step.
+    Continue(Vec<u8>),
+    /// The exchange completed successfully. If present, the contained data
+    /// must be sent to the peer along with the indication of success.
+    Done(Option<Vec<u8>>),
+}
+
+/// A builder for a [`SaslServer`].
+#[derive(Debug)]
+pub struct ServerBuilder {
+    service: String,
+    server_fqdn: Option<String>,
+    user_realm: Option<String>,
+    local_addr: Option<SocketAddr>,
+    remote_addr: Option<SocketAddr>,
+    flags: c_uint,
+    callbacks: Callbacks,
+    security_properties: Option<SecurityProperties>,
+    channel_binding: Option<ChannelBinding>,
+}
+
+impl ServerBuilder {
+    /// Sets the fully-qualified domain name of the server.
+    ///
+    /// If unset, libsasl2 uses the local host name.
+    pub fn server_fqdn(mut self, server_fqdn: &str) -> ServerBuilder {
+        self.server_fqdn = Some(server_fqdn.into());
+        self
+    }
+
+    /// Sets the realm that users belong to when they do not specify one.
+    pub fn user_realm(mut self, user_realm: &str) -> ServerBuilder {
+        self.user_realm = Some(user_realm.into());
+        self
+    }
+
+    /// Sets the local address of the underlying transport.
+    pub fn local_addr(mut self, addr: SocketAddr) -> ServerBuilder {
+        self.local_addr = Some(addr);
+        self
+    }
+
+    /// Sets the remote address of the underlying transport.
+    pub fn remote_addr(mut self, addr: SocketAddr) -> ServerBuilder {
+        self.remote_addr = Some(addr);
+        self
+    }
+
+    /// Sets the callbacks that the server consults during authentication,
+    /// like the proxy authorization policy.
+    pub fn callbacks(mut self, callbacks: Callbacks) -> ServerBuilder {
+        self.callbacks = callbacks;
+        self
+    }
+
+    /// Declares whether the application protocol allows the server to send
+    /// additional data along with its indication of success.
+    ///
+    /// This corresponds to the `SASL_SUCCESS_DATA` flag.
+    pub fn success_data(mut self, enabled: bool) -> ServerBuilder {
+        self.set_flag(SASL_SUCCESS_DATA, enabled);
+        self
+    }
+
+    /// Declares whether the application requires a mechanism that supports
+    /// proxy authorization.
+    ///
+    /// This corresponds to the `SASL_NEED_PROXY` flag.
+    pub fn need_proxy(mut self, enabled: bool) -> ServerBuilder {
+        self.set_flag(SASL_NEED_PROXY, enabled);
+        self
+    }
+
+    /// Sets the security properties that constrain the mechanisms the
+    /// connection will negotiate.
+    ///
+    /// If unset, libsasl2's defaults apply, which permit any mechanism but
+    /// disable security layers.
+    pub fn security_properties(mut self, props: SecurityProperties) -> ServerBuilder {
+        self.security_properties = Some(props);
+        self
+    }
+
+    /// Sets the channel binding data of the underlying secure channel.
+    ///
+    /// Mechanisms that support channel binding, like SCRAM-SHA-256-PLUS,
+    /// include it in the exchange.
+    pub fn channel_binding(mut self, binding: ChannelBinding) -> ServerBuilder {
+        self.channel_binding = Some(binding);
+        self
+    }
+
+    fn set_flag(&mut self, flag: c_uint, enabled: bool) {
+        if enabled {
+            self.flags |= flag;
+        } else {
+            self.flags &= !flag;
+        }
+    }
+
+    /// Creates the server connection.
+    pub fn build(self) -> Result<SaslServer, SaslError> {
+        let context = SaslContext::acquire_server()?;
+        let service = util::to_cstring(&self.service)?;
+        let server_fqdn = self
+            .server_fqdn
+            .as_deref()
+            .map(util::to_cstring)
+            .transpose()?;
+        let user_realm = self
+            .user_realm
+            .as_deref()
+            .map(util::to_cstring)
+            .transpose()?;
+        let local_addr = self.local_addr.map(util::format_addr);
+        let remote_addr = self.remote_addr.map(util::format_addr);
+        self.callbacks.update_log_source(|source| {
+            source.service = Some(self.service.clone());
+            source.remote_addr = remote_addr
+                .as_ref()
+                .map(|addr| addr.to_string_lossy().into_owned());
+        });
+        let raw_callbacks = self.callbacks.to_raw(&[]);
+        let mut conn = ptr::null_mut();
+        let res = unsafe {
+            sasl_server_new(
+                service.as_ptr(),
+                util::opt_ptr(&server_fqdn),
+                util::opt_ptr(&user_realm),
+                util::opt_ptr(&local_addr),
+                util::opt_ptr(&remote_addr),
+                raw_callbacks.as_ptr(),
+                self.flags,
+                &mut conn,
+            )
+        };
+        if res != SASL_OK {
+            // libsasl2 may allocate the connection even when it reports
+            // failure, in which case the connection holds the error detail.
+            let err = if conn.is_null() {
+                SaslError::from_code(res)
+            } else {
+                unsafe {
+                    let err = SaslError::from_conn(conn, res);
+                    sasl_dispose(&mut conn);
+                    err
+                }
+            };
+            return Err(err);
+        }
+        let mut server = SaslServer {
+            conn,
+            complete: false,
+            callbacks: self.callbacks,
+            _raw_callbacks: raw_callbacks,
+            properties: HashMap::new(),
+            aux_property_names: vec![],
+            _context: context,
+        };
+        if let Some(props) = &self.security_properties {
+            server.set_property::<SecProps>(props)?;
+        }
+        if let Some(binding) = &self.channel_binding {
+            server.set_property::<property::ChannelBinding>(binding)?;
+        }
+        Ok(server)
+    }
+}
+
+/// A server connection.
+///
+/// The underlying libsasl2 connection is disposed of when the `SaslServer` is
+/// dropped.
+#[derive(Debug)]
+pub struct SaslServer {
+    conn: *mut sasl_conn_t,
+    complete: bool,
+    // libsasl2 retains pointers to the callback list, and the callback list
+    // to the callbacks' contexts, for the lifetime of the connection.
+    callbacks: Callbacks,
+    _raw_callbacks: Box<[sasl_callback_t]>,
+    // Property values that libsasl2 retains references to.
+    pub(crate) properties: HashMap<c_uint, RawValue>,
+    // Auxiliary property names that libsasl2 retains references to.
+    aux_property_names: Vec<CString>,
+    // Keeps the library initialized until after the connection is disposed
+    // of.
+    _context: SaslContext,
+}
+
+// SAFETY: libsasl2 connections are not tied to the thread that created them,
+// and all access goes through `&mut self`.
+unsafe impl Send for SaslServer {}
+
+impl SaslServer {
+    /// Starts building a server connection for the named service (e.g.,
+    /// `"ldap"` or `"postgres"`).
+    pub fn builder(service: &str) -> ServerBuilder {
+        ServerBuilder {
+            service: service.into(),
+            server_fqdn: None,
+            user_realm: None,
+            local_addr: None,
+            remote_addr: None,
+            flags: 0,
+            callbacks: Callbacks::new(),
+            security_properties: None,
+            channel_binding: None,
+        }
+    }
+
+    /// Begins an authentication exchange using the mechanism selected by the
+    /// client.
+    ///
+    /// `client_data` is the client's initial response, if the application
+    /// protocol allowed it to send one.
+    pub fn start(&mut self, mech: &str, client_data: Option<&[u8]>) -> Result<Step, SaslError> {
+        self.callbacks
+            .update_log_source(|source| source.mechanism = Some(mech.into()));
+        let mech = util::to_cstring(mech)?;
+        let (client_data, client_data_len) = match client_data {
+            None => (ptr::null(), 0),
+            Some(data) => (data.as_ptr() as *const c_char, util::buf_len(data)?),
+        };
+        let mut out = ptr::null();
+        let mut outlen = 0;
+        self.complete = false;
+        let res = unsafe {
+            sasl_server_start(
+                self.conn,
+                mech.as_ptr(),
+                client_data,
+                client_data_len,
+                &mut out,
+                &mut outlen,
+            )
+        };
+        unsafe { self.outcome(res, out, outlen) }
+    }
+
+    /// Advances an authentication exchange with data received from the
+    /// client.
+    pub fn step(&mut self, client_data: &[u8]) -> Result<Step, SaslError> {
+        let client_data_len = util::buf_len(client_data)?;
+        let mut out = ptr::null();
+        let mut outlen = 0;
+        let res = unsafe {
+            sasl_server_step(
+                self.conn,
+                client_data.as_ptr() as *const c_char,
+                client_data_len,
+                &mut out,
+                &mut outlen,
+            )
+        };
+        unsafe { self.outcome(res, out, outlen) }
+    }
+
+    /// Reports whether the authentication exchange has completed
+    /// successfully.
+    pub fn is_complete(&self) -> bool {
+        self.complete
+    }
+
+    /// Returns the authorization identity of the authenticated user.
+    ///
+    /// This is the identity that the client is acting as, which may differ
+    /// from the [authentication identity](SaslServer::auth_user) if the
+    /// mechanism supports proxy authorization.
+    ///
+    /// Returns `None` if the exchange has not completed.
+    pub fn username(&self) -> Option<String> {
+        self.complete_prop_str(SASL_USERNAME)
+    }
+
+    /// Returns the authentication identity of the authenticated user, i.e.,
+    /// the identity whose credentials were verified.
+    ///
+    /// Returns `None` if the exchange has not completed.
+    pub fn auth_user(&self) -> Option<String> {
+        self.complete_prop_str(SASL_AUTHUSER)
+    }
+
+    /// Returns the name of the negotiated mechanism.
+    ///
+    /// Returns `None` if the exchange has not completed.
+    pub fn mechanism(&self) -> Option<String> {
+        self.complete_prop_str(SASL_MECHNAME)
+    }
+
+    /// Returns the security strength factor of the negotiated security layer,
+    /// or zero if no security layer is in effect.
+    ///
+    /// Returns `None` if the exchange has not completed.
+    pub fn ssf(&self) -> Option<sasl_ssf_t> {
+        if !self.complete {
+            return None;
+        }
+        unsafe { util::getprop(self.conn, SASL_SSF).map(|ssf| *(ssf as *const sasl_ssf_t)) }
+    }
+
+    /// Requests the named auxiliary properties of the user, in addition to
+    /// any already requested.
+    ///
+    /// Requests must be made before the exchange starts.
+    pub fn request_aux_properties(&mut self, names: &[&str]) -> Result<(), SaslError> {
+        let names = names
+            .iter()
+            .map(|name| util::to_cstring(name))
+            .collect::<Result<Vec<_>, _>>()?;
+        let mut ptrs: Vec<_> = names.iter().map(|name| name.as_ptr()).collect();
+        ptrs.push(ptr::null());
+        let res = unsafe { sasl_auxprop_request(self.conn, ptrs.as_mut_ptr()) };
+        unsafe { error::check(self.conn, res) }?;
+        // libsasl2 retains the names, but not the array.
+        self.aux_property_names.extend(names);
+        Ok(())
+    }
+
+    /// Returns the auxiliary properties of the user.
+    ///
+    /// Values are only available once the exchange has looked up the user.
+    pub fn aux_properties(&mut self) -> AuxProps<'_> {
+        unsafe { AuxProps::from_raw(sasl_auxprop_getctx(self.conn)) }
+    }
+
+    /// Checks a plaintext password for `user` outside of an authentication
+    /// exchange, e.g., for protocols that receive credentials directly.
+    ///
+    /// The password is verified like those received by the PLAIN mechanism:
+    /// by the [`PasswordVerifier`], if one is registered, and then by the
+    /// methods configured with `pwcheck_method`. Errors include
+    /// [`SaslError::NoUser`], [`SaslError::BadAuth`],
+    /// [`SaslError::Disabled`], and [`SaslError::Expired`].
+    ///
+    /// `password` is copied in order to nul-terminate it for libsasl2, and
+    /// that copy is erased afterwards. Erasing `password` itself is left to
+    /// the caller.
+    ///
+    /// [`PasswordVerifier`]: crate::userdb::PasswordVerifier
+    pub fn check_password(&mut self, user: &str, password: &[u8]) -> Result<(), SaslError> {
+        let user = util::to_cstring(user)?;
+        let user_len = util::buf_len(user.as_bytes())?;
+        let pass_len = util::buf_len(password)?;
+        // Some password backends expect a nul-terminated password.
+        let mut pass = Vec::with_capacity(password.len() + 1);
+        pass.extend_from_slice(password);
+        pass.push(0);
+        let res = unsafe {
+            sasl_checkpass(
+                self.conn,
+                user.as_ptr(),
+                user_len,
+                pass.as_ptr() as *const c_char,
+                pass_len,
+            )
+        };
+        unsafe { sasl_erasebuffer(pass.as_mut_ptr() as *mut c_char, pass.len() as c_uint) };
+        unsafe { self.check(res) }?;
+        Ok(())
+    }
+
+    /// Checks that `user` exists, according to the methods configured with
+    /// `pwcheck_method`, and returns a [`SaslError::NoUser`] error if not.
+    ///
+    /// `service` and `realm` default to those of the connection. Any
+    /// [`PasswordVerifier`](crate::userdb::PasswordVerifier) is not
+    /// consulted.
+    pub fn user_exists(
+        &mut self,
+        service: Option<&str>,
+        realm: Option<&str>,
+        user: &str,
+    ) -> Result<(), SaslError> {
+        let service = service.map(util::to_cstring).transpose()?;
+        let realm = realm.map(util::to_cstring).transpose()?;
+        let user = util::to_cstring(user)?;
+        let res = unsafe {
+            sasl_user_exists(
+                self.conn,
+                util::opt_ptr(&service),
+                util::opt_ptr(&realm),
+                user.as_ptr(),
+            )
+        };
+        unsafe { error::check(self.conn, res) }?;
+        Ok(())
+    }
+
+    /// Sets the password of `user`, or disables the user if `flags` includes
+    /// [`SetPasswordFlags::DISABLE`].
+    ///
+    /// The password is handed to the [`PasswordStore`], if one is
+    /// registered, to any auxiliary property plugin that can store
+    /// properties, and to each mechanism that derives its own verifier from
+    /// it, like SCRAM. `old` is the user's current password, which some
+    /// mechanisms require.
+    ///
+    /// [`PasswordStore`]: crate::userdb::PasswordStore
+    pub fn set_password(
+        &mut self,
+        user: &str,
+        new: &[u8],
+        old: Option<&[u8]>,
+        flags: SetPasswordFlags,
+    ) -> Result<(), SaslError> {
+        let user = util::to_cstring(user)?;
+        let (old, old_len) = match old {
+            None => (ptr::null(), 0),
+            Some(old) => (old.as_ptr() as *const c_char, util::buf_len(old)?),
+        };
+        let res = unsafe {
+            sasl_setpass(
+                self.conn,
+                user.as_ptr(),
+                new.as_ptr() as *const c_char,
+                util::buf_len(new)?,
+                old,
+                old_len,
+                flags.bits(),
+            )
+        };
+        unsafe { error::check(self.conn, res) }?;
+        Ok(())
+    }
+
+    /// Returns a pointer to the underlying libsasl2 connection.
+    ///
+    /// This is an escape hatch for functionality that is not yet exposed
+    /// safely. The pointer remains owned by the `SaslServer`, and must not be
+    /// disposed of or used after the `SaslServer` is dropped.
+    pub fn as_ptr(&self) -> *mut sasl_conn_t {
+        self.conn
+    }
+
+    fn complete_prop_str(&self, prop: c_uint) -> Option<String> {
+        if !self.complete {
+            return None;
+        }
+        unsafe { util::getprop_str(self.conn, prop) }
+    }
+
+    /// Translates the result of `sasl_server_start` or `sasl_server_step`
+    /// into a [`Step`], copying any output out of libsasl2's buffers.
+    unsafe fn outcome(
+        &mut self,
+        res: c_int,
+        out: *const c_char,
+        outlen: c_uint,
+    ) -> Result<Step, SaslError> {
+        match self.check(res)? {
+            Status::Ok => {
+                self.complete = true;
+                if out.is_null() {
+                    Ok(Step::Done(None))
+                } else {
+                    Ok(Step::Done(Some(util::copy_buf(out, outlen))))
+                }
+            }
+            Status::Continue => Ok(Step::Continue(util::copy_buf(out, outlen))),
+            // Server-side exchanges never require interaction.
+            Status::Interact => Err(SaslError::bad_prot(
+                "mechanism requested interaction, which servers do not support",
+            )),
+        }
+    }
+
+    /// Like [`error::check`], but reports why the password verifier rejected
+    /// a password, rather than why the methods libsasl2 fell back to did.
+    unsafe fn check(&self, res: c_int) -> Result<Status, SaslError> {
+        let failure = self.callbacks.take_auth_failure();
+        match (error::check(self.conn, res), failure) {
+            (Err(_), Some(failure)) => Err(SaslError::from_conn(self.conn, failure.code())),
+            (status, _) => status,
+        }
+    }
+}
+
+impl Drop for SaslServer {
+    fn drop(&mut self) {
+        unsafe { sasl_dispose(&mut self.conn) }
+    }
+}
@@ -0,0 +1,161 @@
+// Copyright Materialize, Inc. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License in the LICENSE file at the
+// root of this repository, or online at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! The outcome of a completed exchange.
+
+use std::mem;
+use std::ptr;
+
+use libc::c_uint;
+use sasl2_sys::sasl::{
+    sasl_conn_t, sasl_ssf_t, sasl_version_info, SASL_AUTHUSER, SASL_MAXOUTBUF, SASL_MECHNAME,
+    SASL_NOTDONE, SASL_SSF, SASL_USERNAME,
+};
+use sasl2_sys::saslplug::{sasl_out_params_t, SASL_CB_DISP_USED, SASL_CB_DISP_WANT};
+
+use crate::error::SaslError;
+use crate::util;
+
+/// Whether channel binding was used by a completed exchange.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ChannelBindingDisposition {
+    /// The client did not support channel binding (`SASL_CB_DISP_NONE`).
+    Absent,
+    /// The client supported channel binding, but believed that the server
+    /// did not (`SASL_CB_DISP_WANT`).
+    ///
+    /// If the server did support channel binding, the mechanism list
+    /// presented to the client may have been tampered with.
+    Wanted,
+    /// Channel binding was used (`SASL_CB_DISP_USED`).
+    Used,
+}
+
+/// Information about a completed exchange, as recorded in libsasl2's
+/// `sasl_out_params_t`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SessionInfo {
+    mechanism: String,
+    username: Option<String>,
+    auth_user: Option<String>,
+    ssf: sasl_ssf_t,
+    max_out_buf: c_uint,
+    channel_binding: ChannelBindingDisposition,
+    channel_binding_name: Option<String>,
+}
+
+impl SessionInfo {
+    /// Reads the session information of `conn`, returning a
+    /// [`SaslError::NotDone`] error if the exchange has not completed, or a
+    /// [`SaslError::BadVers`] error if the linked libsasl2 is not the
+    /// release whose internals it relies on.
+    ///
+    /// # Safety
+    ///
+    /// `conn` must be a valid connection.
+    pub(crate) unsafe fn from_conn(conn: *mut sasl_conn_t) -> Result<SessionInfo, SaslError> {
+        let not_done = || SaslError::from_code(SASL_NOTDONE);
+        // There are no properties for whether the exchange is done or for
+        // the channel binding disposition, so these are read from libsasl2's
+        // output parameters, which are located through the value of
+        // `SASL_MAXOUTBUF`. That relies on the internals of a particular
+        // release, and a system library may be any other.
+        let (mut major, mut minor, mut step) = (0, 0, 0);
+        sasl_version_info(
+            ptr::null_mut(),
+            ptr::null_mut(),
+            &mut major,
+            &mut minor,
+            &mut step,
+            ptr::null_mut(),
+        );
+        if (major, minor, step) != (2, 1, 28) {
+            return Err(SaslError::bad_vers(&format!(
+                "session information requires libsasl2 2.1.28, not {}.{}.{}",
+                major, minor, step
+            )));
+        }
+        // SAFETY: in libsasl2 2.1.28, the release that sasl2-sys vendors,
+        // `sasl_getprop` (lib/common.c) answers `SASL_MAXOUTBUF` with
+        // `&conn->oparams.maxoutbuf`, where `conn->oparams` is the
+        // connection's `sasl_out_params_t`. Stepping back by the offset of the
+        // field therefore yields a pointer to that struct, which lives as long
+        // as `conn`.
+        let max_out_buf = util::getprop(conn, SASL_MAXOUTBUF).ok_or_else(not_done)?;
+        let offset = mem::offset_of!(sasl_out_params_t, maxoutbuf);
+        let oparams = &*((max_out_buf as *const u8).sub(offset) as *const sasl_out_params_t);
+        if oparams.doneflag == 0 {
+            return Err(not_done());
+        }
+        let channel_binding = match oparams.cbindingdisp {
+            SASL_CB_DISP_USED => ChannelBindingDisposition::Used,
+            SASL_CB_DISP_WANT => ChannelBindingDisposition::Wanted,
+            _ => ChannelBindingDisposition::Absent,
+        };
+        let ssf = util::getprop(conn, SASL_SSF).ok_or_else(not_done)?;
+        Ok(SessionInfo {
+            mechanism: util::getprop_str(conn, SASL_MECHNAME).ok_or_else(not_done)?,
+            username: util::getprop_str(conn, SASL_USERNAME),
+            auth_user: util::getprop_str(conn, SASL_AUTHUSER),
+            ssf: *(ssf as *const sasl_ssf_t),
+            max_out_buf: *(max_out_buf as *const c_uint),
+            channel_binding,
+            channel_binding_name: match channel_binding {
+                ChannelBindingDisposition::Used => util::copy_str(oparams.cbindingname),
+                _ => None,
+            },
+        })
+    }
+
+    /// Returns the name of the negotiated mechanism.
+    pub fn mechanism(&self) -> &str {
+        &self.mechanism
+    }
+
+    /// Returns the authorization identity of the authenticated user, if the
+    /// mechanism established one.
+    pub fn username(&self) -> Option<&str> {
+        self.username.as_deref()
+    }
+
+    /// Returns the authentication identity of the authenticated user, if the
+    /// mechanism established one.
+    pub fn auth_user(&self) -> Option<&str> {
+        self.auth_user.as_deref()
+    }
+
+    /// Returns the security strength factor of the negotiated security layer,
+    /// or zero if no security layer is in effect.
+    pub fn ssf(&self) -> sasl_ssf_t {
+        self.ssf
+    }
+
+    /// Returns the size of the largest buffer that may be passed to
+    /// [`Connection::encode`](crate::connection::Connection::encode).
+    pub fn max_out_buf(&self) -> c_uint {
+        self.max_out_buf
+    }
+
+    /// Returns whether channel binding was used.
+    pub fn channel_binding(&self) -> ChannelBindingDisposition {
+        self.channel_binding
+    }
+
+    /// Returns the type of the channel binding, e.g.,
+    /// `"tls-server-end-point"`, if channel binding was used.
+    pub fn channel_binding_name(&self) -> Option<&str> {
+        self.channel_binding_name.as_deref()
+    }
+}
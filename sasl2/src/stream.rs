@@ -0,0 +1,591 @@
+// Copyright Materialize, Inc. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License in the LICENSE file at the
+// root of this repository, or online at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Security layers.
+//!
+//! Once an exchange negotiates a security layer (i.e., a security strength
+//! factor greater than zero), all further traffic must be protected by the
+//! mechanism. On the wire, each protected buffer is preceded by its length as
+//! a 4-byte big-endian integer.
+//!
+//! [`SaslStream`] wraps a transport and performs this protection and framing
+//! transparently. If no security layer was negotiated, it passes data through
+//! unchanged.
+
+use std::cmp;
+use std::io::{self, IoSlice, Read, Write};
+
+use libc::c_uint;
+use sasl2_sys::sasl::{
+    sasl_security_properties, sasl_ssf_t, SASL_MAXOUTBUF, SASL_SEC_PROPS, SASL_SSF,
+};
+
+use crate::connection::Connection;
+use crate::error::SaslError;
+use crate::util;
+
+/// The length of the header that precedes each frame.
+const HEADER_LEN: usize = 4;
+
+/// The transformations applied by a security layer.
+///
+/// Every [`Connection`] is a codec. The framing logic is written against this
+/// trait so that it can be exercised without negotiating a security layer.
+pub(crate) trait Codec {
+    fn encode(&mut self, data: &[u8]) -> Result<Vec<u8>, SaslError>;
+    fn encode_vectored(&mut self, bufs: &[IoSlice<'_>]) -> Result<Vec<u8>, SaslError>;
+    fn decode(&mut self, data: &[u8]) -> Result<Vec<u8>, SaslError>;
+}
+
+impl<C> Codec for C
+where
+    C: Connection,
+{
+    fn encode(&mut self, data: &[u8]) -> Result<Vec<u8>, SaslError> {
+        Connection::encode(self, data)
+    }
+
+    fn encode_vectored(&mut self, bufs: &[IoSlice<'_>]) -> Result<Vec<u8>, SaslError> {
+        Connection::encode_vectored(self, bufs)
+    }
+
+    fn decode(&mut self, data: &[u8]) -> Result<Vec<u8>, SaslError> {
+        Connection::decode(self, data)
+    }
+}
+
+/// The framing state of a security layer, independent of how the underlying
+/// transport is driven.
+#[derive(Debug)]
+pub(crate) struct Layer {
+    /// The maximum number of bytes to encode at once.
+    max_out: usize,
+    /// The maximum size of a frame that the peer may send.
+    max_in: usize,
+    /// The frame being read, including its header.
+    frame: Vec<u8>,
+    filled: usize,
+    header_read: bool,
+    /// Decoded data that has not yet been returned to the reader.
+    decoded: Vec<u8>,
+    decoded_pos: usize,
+    /// Encoded data that has not yet been written to the transport.
+    encoded: Vec<u8>,
+    encoded_pos: usize,
+}
+
+impl Layer {
+    /// Returns the security layer negotiated on `conn`, or `None` if there is
+    /// none.
+    pub(crate) fn negotiated<C>(conn: &C) -> Result<Option<Layer>, SaslError>
+    where
+        C: Connection,
+    {
+        let conn = conn.as_ptr();
+        unsafe {
+            let ssf = match util::getprop(conn, SASL_SSF) {
+                Some(ssf) => *(ssf as *const sasl_ssf_t),
+                None => 0,
+            };
+            if ssf == 0 {
+                return Ok(None);
+            }
+            let max_out = match util::getprop(conn, SASL_MAXOUTBUF) {
+                Some(max_out) => *(max_out as *const c_uint),
+                None => return Err(SaslError::bad_param("no maximum output size negotiated")),
+            };
+            let max_in = match util::getprop(conn, SASL_SEC_PROPS) {
+                Some(props) => (*(props as *const sasl_security_properties)).maxbufsize,
+                None => return Err(SaslError::bad_param("no maximum input size negotiated")),
+            };
+            Ok(Some(Layer::new(max_out as usize, max_in as usize)))
+        }
+    }
+
+    /// Returns a security layer that encodes at most `max_out` bytes at once
+    /// and accepts frames of at most `max_in` bytes.
+    pub(crate) fn new(max_out: usize, max_in: usize) -> Layer {
+        Layer {
+            max_out: cmp::max(max_out, 1),
+            max_in,
+            frame: vec![0; HEADER_LEN],
+            filled: 0,
+            header_read: false,
+            decoded: vec![],
+            decoded_pos: 0,
+            encoded: vec![],
+            encoded_pos: 0,
+        }
+    }
+
+    /// Reports whether decoded data is waiting to be read.
+    pub(crate) fn has_decoded(&self) -> bool {
+        self.decoded_pos < self.decoded.len()
+    }
+
+    /// Copies as much waiting decoded data as fits into `buf`.
+    pub(crate) fn read_decoded(&mut self, buf: &mut [u8]) -> usize {
+        let data = &self.decoded[self.decoded_pos..];
+        let n = cmp::min(data.len(), buf.len());
+        buf[..n].copy_from_slice(&data[..n]);
+        self.decoded_pos += n;
+        n
+    }
+
+    /// Returns the part of the current frame that has yet to be read from
+    /// the transport.
+    pub(crate) fn unfilled(&mut self) -> &mut [u8] {
+        &mut self.frame[self.filled..]
+    }
+
+    /// Reports whether the transport is positioned between frames, where it
+    /// may cleanly reach end of file.
+    pub(crate) fn at_boundary(&self) -> bool {
+        self.filled == 0
+    }
+
+    /// Records that `n` more bytes of the current frame were read, and
+    /// decodes the frame if it is complete.
+    pub(crate) fn advance<C>(&mut self, conn: &mut C, n: usize) -> io::Result<()>
+    where
+        C: Codec,
+    {
+        self.filled += n;
+        if !self.header_read && self.filled == HEADER_LEN {
+            let mut header = [0; HEADER_LEN];
+            header.copy_from_slice(&self.frame);
+            let len = u32::from_be_bytes(header) as usize;
+            if len > self.max_in {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!(
+                        "frame of {} bytes exceeds maximum of {} bytes",
+                        len, self.max_in
+                    ),
+                ));
+            }
+            self.frame.resize(HEADER_LEN + len, 0);
+            self.header_read = true;
+        }
+        if self.header_read && self.filled == self.frame.len() {
+            // Mechanisms expect to decode the frame including its header.
+            self.decoded = conn.decode(&self.frame)?;
+            self.decoded_pos = 0;
+            self.frame.truncate(HEADER_LEN);
+            self.filled = 0;
+            self.header_read = false;
+        }
+        Ok(())
+    }
+
+    /// Encodes as much of `buf` as the negotiated maximum output size allows,
+    /// returning the number of bytes consumed.
+    ///
+    /// Must only be called once all previously encoded data has been written.
+    pub(crate) fn encode<C>(&mut self, conn: &mut C, buf: &[u8]) -> io::Result<usize>
+    where
+        C: Codec,
+    {
+        debug_assert!(self.encoded().is_empty());
+        let n = cmp::min(buf.len(), self.max_out);
+        // The mechanism frames its output itself.
+        self.encoded = conn.encode(&buf[..n])?;
+        self.encoded_pos = 0;
+        Ok(n)
+    }
+
+    /// Like [`Layer::encode`], but encodes as much of the concatenation of
+    /// `bufs` as the negotiated maximum output size allows.
+    pub(crate) fn encode_vectored<C>(
+        &mut self,
+        conn: &mut C,
+        bufs: &[IoSlice<'_>],
+    ) -> io::Result<usize>
+    where
+        C: Codec,
+    {
+        debug_assert!(self.encoded().is_empty());
+        let mut n = 0;
+        let mut chunk = vec![];
+        for buf in bufs {
+            let len = cmp::min(buf.len(), self.max_out - n);
+            chunk.push(IoSlice::new(&buf[..len]));
+            n += len;
+            if n == self.max_out {
+                break;
+            }
+        }
+        self.encoded = conn.encode_vectored(&chunk)?;
+        self.encoded_pos = 0;
+        Ok(n)
+    }
+
+    /// Returns the encoded data that has yet to be written to the transport.
+    pub(crate) fn encoded(&self) -> &[u8] {
+        &self.encoded[self.encoded_pos..]
+    }
+
+    /// Records that `n` bytes of encoded data were written.
+    pub(crate) fn consume_encoded(&mut self, n: usize) {
+        self.encoded_pos += n;
+    }
+}
+
+/// A stream protected by a negotiated security layer.
+///
+/// Writes are encoded in chunks of at most the peer's maximum buffer size
+/// (`SASL_MAXOUTBUF`), using `sasl_encodev` for vectored writes, and reads
+/// are decoded a frame at a time. Frames larger than the local maximum buffer
+/// size are rejected with [`io::ErrorKind::InvalidData`].
+///
+/// Encoded data is buffered internally when the transport does not accept it
+/// all at once, so [`flush`](Write::flush) must be called to ensure it is
+/// written.
+#[derive(Debug)]
+pub struct SaslStream<C, S> {
+    conn: C,
+    inner: S,
+    layer: Option<Layer>,
+}
+
+impl<C, S> SaslStream<C, S>
+where
+    C: Connection,
+{
+    /// Wraps `inner` in the security layer negotiated by the completed
+    /// exchange on `conn`.
+    pub fn new(conn: C, inner: S) -> Result<SaslStream<C, S>, SaslError> {
+        let layer = Layer::negotiated(&conn)?;
+        Ok(SaslStream { conn, inner, layer })
+    }
+
+    /// Reports whether a security layer is in effect, i.e., whether data is
+    /// encoded rather than passed through unchanged.
+    pub fn is_protected(&self) -> bool {
+        self.layer.is_some()
+    }
+
+    /// Returns a reference to the connection.
+    pub fn connection(&self) -> &C {
+        &self.conn
+    }
+
+    /// Returns a reference to the underlying stream.
+    pub fn get_ref(&self) -> &S {
+        &self.inner
+    }
+
+    /// Returns a mutable reference to the underlying stream.
+    ///
+    /// Reading from or writing to the underlying stream directly will corrupt
+    /// the framing of the security layer.
+    pub fn get_mut(&mut self) -> &mut S {
+        &mut self.inner
+    }
+
+    /// Unwraps the stream, returning the connection and the underlying
+    /// stream.
+    ///
+    /// Any buffered data is lost.
+    pub fn into_inner(self) -> (C, S) {
+        (self.conn, self.inner)
+    }
+}
+
+impl<C, S> Read for SaslStream<C, S>
+where
+    C: Connection,
+    S: Read,
+{
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match &mut self.layer {
+            None => self.inner.read(buf),
+            Some(layer) => read_and_decode(layer, &mut self.conn, &mut self.inner, buf),
+        }
+    }
+}
+
+impl<C, S> Write for SaslStream<C, S>
+where
+    C: Connection,
+    S: Write,
+{
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match &mut self.layer {
+            None => self.inner.write(buf),
+            Some(layer) => encode_and_write(layer, &mut self.conn, &mut self.inner, buf),
+        }
+    }
+
+    fn write_vectored(&mut self, bufs: &[IoSlice<'_>]) -> io::Result<usize> {
+        match &mut self.layer {
+            None => self.inner.write_vectored(bufs),
+            Some(layer) => encode_and_write_vectored(layer, &mut self.conn, &mut self.inner, bufs),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        if let Some(layer) = &mut self.layer {
+            write_encoded(layer, &mut self.inner)?;
+        }
+        self.inner.flush()
+    }
+}
+
+/// Reads from `inner` until a frame is decoded, and copies as much of the
+/// decoded data as fits into `buf`.
+fn read_and_decode<C, S>(
+    layer: &mut Layer,
+    conn: &mut C,
+    inner: &mut S,
+    buf: &mut [u8],
+) -> io::Result<usize>
+where
+    C: Codec,
+    S: Read,
+{
+    loop {
+        if layer.has_decoded() || buf.is_empty() {
+            return Ok(layer.read_decoded(buf));
+        }
+        let n = inner.read(layer.unfilled())?;
+        if n == 0 {
+            if layer.at_boundary() {
+                return Ok(0);
+            }
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "stream ended within a frame",
+            ));
+        }
+        layer.advance(conn, n)?;
+    }
+}
+
+/// Encodes a prefix of `buf` and writes it to `inner`, returning the number
+/// of bytes consumed.
+fn encode_and_write<C, S>(
+    layer: &mut Layer,
+    conn: &mut C,
+    inner: &mut S,
+    buf: &[u8],
+) -> io::Result<usize>
+where
+    C: Codec,
+    S: Write,
+{
+    write_encoded(layer, inner)?;
+    if buf.is_empty() {
+        return Ok(0);
+    }
+    let n = layer.encode(conn, buf)?;
+    // `buf` has been consumed, so any error writing out the encoded data is
+    // left to be reported by the next write or flush, which will encounter it
+    // again.
+    let _ = write_encoded(layer, inner);
+    Ok(n)
+}
+
+/// Like [`encode_and_write`], but for the concatenation of `bufs`.
+fn encode_and_write_vectored<C, S>(
+    layer: &mut Layer,
+    conn: &mut C,
+    inner: &mut S,
+    bufs: &[IoSlice<'_>],
+) -> io::Result<usize>
+where
+    C: Codec,
+    S: Write,
+{
+    write_encoded(layer, inner)?;
+    if bufs.iter().all(|buf| buf.is_empty()) {
+        return Ok(0);
+    }
+    let n = layer.encode_vectored(conn, bufs)?;
+    // See `encode_and_write`.
+    let _ = write_encoded(layer, inner);
+    Ok(n)
+}
+
+/// Writes all buffered encoded data to `inner`.
+fn write_encoded<S>(layer: &mut Layer, inner: &mut S) -> io::Result<()>
+where
+    S: Write,
+{
+    while !layer.encoded().is_empty() {
+        match inner.write(layer.encoded())? {
+            0 => {
+                return Err(io::Error::new(
+                    io::ErrorKind::WriteZero,
+                    "failed to write encoded data",
+                ))
+            }
+            n => layer.consume_encoded(n),
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use std::convert::TryInto;
+    use std::io::Cursor;
+
+    use super::*;
+
+    /// A codec that frames data without transforming it.
+    #[derive(Debug)]
+    pub(crate) struct Framing;
+
+    impl Codec for Framing {
+        fn encode(&mut self, data: &[u8]) -> Result<Vec<u8>, SaslError> {
+            let mut out = (data.len() as u32).to_be_bytes().to_vec();
+            out.extend_from_slice(data);
+            Ok(out)
+        }
+
+        fn encode_vectored(&mut self, bufs: &[IoSlice<'_>]) -> Result<Vec<u8>, SaslError> {
+            let data: Vec<u8> = bufs.iter().flat_map(|buf| buf.iter().copied()).collect();
+            self.encode(&data)
+        }
+
+        fn decode(&mut self, data: &[u8]) -> Result<Vec<u8>, SaslError> {
+            let len = u32::from_be_bytes(data[..HEADER_LEN].try_into().unwrap()) as usize;
+            assert_eq!(len, data.len() - HEADER_LEN, "decoded a partial frame");
+            Ok(data[HEADER_LEN..].to_vec())
+        }
+    }
+
+    /// Returns the payload lengths of the frames in `wire`.
+    pub(crate) fn frame_lens(mut wire: &[u8]) -> Vec<usize> {
+        let mut lens = vec![];
+        while !wire.is_empty() {
+            let len = u32::from_be_bytes(wire[..HEADER_LEN].try_into().unwrap()) as usize;
+            lens.push(len);
+            wire = &wire[HEADER_LEN + len..];
+        }
+        lens
+    }
+
+    /// A reader that returns at most `limit` bytes per read.
+    struct Trickle {
+        inner: Cursor<Vec<u8>>,
+        limit: usize,
+    }
+
+    impl Read for Trickle {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            let n = cmp::min(buf.len(), self.limit);
+            self.inner.read(&mut buf[..n])
+        }
+    }
+
+    fn read_all<S>(layer: &mut Layer, inner: &mut S) -> io::Result<Vec<u8>>
+    where
+        S: Read,
+    {
+        let mut out = vec![];
+        let mut buf = [0; 3];
+        loop {
+            match read_and_decode(layer, &mut Framing, inner, &mut buf)? {
+                0 => return Ok(out),
+                n => out.extend_from_slice(&buf[..n]),
+            }
+        }
+    }
+
+    #[test]
+    fn test_read_split_header() {
+        let mut wire = Framing.encode(b"hello, ").unwrap();
+        wire.extend(Framing.encode(b"world").unwrap());
+        for limit in 1..wire.len() {
+            let mut layer = Layer::new(16, 16);
+            let mut inner = Trickle {
+                inner: Cursor::new(wire.clone()),
+                limit,
+            };
+            assert_eq!(read_all(&mut layer, &mut inner).unwrap(), b"hello, world");
+        }
+    }
+
+    #[test]
+    fn test_read_oversized_frame() {
+        let mut layer = Layer::new(16, 4);
+        let mut inner = Cursor::new(Framing.encode(b"hello").unwrap());
+        let err = read_all(&mut layer, &mut inner).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+
+        let mut layer = Layer::new(16, 5);
+        let mut inner = Cursor::new(Framing.encode(b"hello").unwrap());
+        assert_eq!(read_all(&mut layer, &mut inner).unwrap(), b"hello");
+    }
+
+    #[test]
+    fn test_read_eof_within_frame() {
+        let wire = Framing.encode(b"hello").unwrap();
+        for len in 1..wire.len() {
+            let mut layer = Layer::new(16, 16);
+            let mut inner = Cursor::new(&wire[..len]);
+            let err = read_all(&mut layer, &mut inner).unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+        }
+
+        let mut layer = Layer::new(16, 16);
+        let mut inner = Cursor::new(&[][..]);
+        assert_eq!(read_all(&mut layer, &mut inner).unwrap(), b"");
+    }
+
+    #[test]
+    fn test_write_splits_frames() {
+        let mut layer = Layer::new(5, 16);
+        let mut wire = vec![];
+        let mut data = &b"hello, world"[..];
+        while !data.is_empty() {
+            let n = encode_and_write(&mut layer, &mut Framing, &mut wire, data).unwrap();
+            data = &data[n..];
+        }
+        assert_eq!(frame_lens(&wire), [5, 5, 2]);
+
+        let mut layer = Layer::new(16, 5);
+        let mut inner = Cursor::new(wire);
+        assert_eq!(read_all(&mut layer, &mut inner).unwrap(), b"hello, world");
+    }
+
+    #[test]
+    fn test_write_vectored_splits_frames() {
+        let data: [&[u8]; 4] = [b"hello", b", ", b"", b"world"];
+        let mut layer = Layer::new(5, 16);
+        let mut wire = vec![];
+        let mut written = 0;
+        while written < 12 {
+            let mut skip = written;
+            let bufs: Vec<_> = data
+                .iter()
+                .map(|buf| {
+                    let n = cmp::min(skip, buf.len());
+                    skip -= n;
+                    IoSlice::new(&buf[n..])
+                })
+                .collect();
+            written +=
+                encode_and_write_vectored(&mut layer, &mut Framing, &mut wire, &bufs).unwrap();
+        }
+        assert_eq!(frame_lens(&wire), [5, 5, 2]);
+
+        let mut layer = Layer::new(16, 5);
+        let mut inner = Cursor::new(wire);
+        assert_eq!(read_all(&mut layer, &mut inner).unwrap(), b"hello, world");
+    }
+}
@@ -0,0 +1,212 @@
+// Copyright Materialize, Inc. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License in the LICENSE file at the
+// root of this repository, or online at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Application-provided user databases.
+//!
+//! Mechanisms that receive plaintext passwords, like PLAIN and LOGIN, ask
+//! libsasl2 to verify them. A [`PasswordVerifier`] installed with
+//! [`Callbacks::password_verifier`] lets the application check them against
+//! its own user store (`SASL_CB_SERVER_USERDB_CHECKPASS`). Likewise, a
+//! [`PasswordStore`] installed with [`Callbacks::password_store`] receives
+//! the passwords set with [`SaslServer::set_password`]
+//! (`SASL_CB_SERVER_USERDB_SETPASS`).
+//!
+//! ```no_run
+//! use sasl2::auxprop::AuxProps;
+//! use sasl2::callbacks::{Callbacks, ConnectionInfo};
+//! use sasl2::server::SaslServer;
+//! use sasl2::userdb::{self, AuthFailure, PasswordVerifier};
+//!
+//! struct Catalog;
+//!
+//! impl PasswordVerifier for Catalog {
+//!     fn check(
+//!         &self,
+//!         _conn: &ConnectionInfo,
+//!         user: &str,
+//!         password: &[u8],
+//!         _props: &mut AuxProps,
+//!     ) -> Result<(), AuthFailure> {
+//!         match user {
+//!             "alice" if userdb::constant_time_eq(password, b"secret") => Ok(()),
+//!             "alice" => Err(AuthFailure::BadPassword),
+//!             _ => Err(AuthFailure::NoUser),
+//!         }
+//!     }
+//! }
+//!
+//! let server = SaslServer::builder("postgres")
+//!     .callbacks(Callbacks::new().password_verifier(Catalog))
+//!     .build()?;
+//! # Ok::<(), sasl2::error::SaslError>(())
+//! ```
+//!
+//! [`Callbacks::password_verifier`]: crate::callbacks::Callbacks::password_verifier
+//! [`Callbacks::password_store`]: crate::callbacks::Callbacks::password_store
+//! [`SaslServer::set_password`]: crate::server::SaslServer::set_password
+
+use bitflags::bitflags;
+use libc::{c_int, c_uint};
+use sasl2_sys::sasl::{
+    SASL_BADAUTH, SASL_CONSTRAINT_VIOLAT, SASL_DISABLED, SASL_EXPIRED, SASL_NEED_OLD_PASSWD,
+    SASL_NOUSER, SASL_NOVERIFY, SASL_PWLOCK, SASL_SET_CREATE, SASL_SET_CURMECH_ONLY,
+    SASL_SET_DISABLE, SASL_SET_NOPLAIN, SASL_UNAVAIL, SASL_WEAKPASS,
+};
+
+use crate::auxprop::AuxProps;
+use crate::callbacks::ConnectionInfo;
+
+/// The reason a password was rejected.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum AuthFailure {
+    /// The user does not exist (`SASL_NOUSER`).
+    NoUser,
+    /// The password is incorrect (`SASL_BADAUTH`).
+    BadPassword,
+    /// The account is disabled (`SASL_DISABLED`).
+    Disabled,
+    /// The password has expired and must be reset (`SASL_EXPIRED`).
+    Expired,
+    /// The user exists, but has no password (`SASL_NOVERIFY`).
+    NoVerifier,
+    /// The user store is unavailable (`SASL_UNAVAIL`).
+    Unavailable,
+}
+
+impl AuthFailure {
+    pub(crate) fn code(self) -> c_int {
+        match self {
+            AuthFailure::NoUser => SASL_NOUSER,
+            AuthFailure::BadPassword => SASL_BADAUTH,
+            AuthFailure::Disabled => SASL_DISABLED,
+            AuthFailure::Expired => SASL_EXPIRED,
+            AuthFailure::NoVerifier => SASL_NOVERIFY,
+            AuthFailure::Unavailable => SASL_UNAVAIL,
+        }
+    }
+}
+
+/// The reason a password change was rejected.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SetPasswordFailure {
+    /// The user does not exist (`SASL_NOUSER`).
+    NoUser,
+    /// The account is disabled (`SASL_DISABLED`).
+    Disabled,
+    /// The password is locked (`SASL_PWLOCK`).
+    PasswordLocked,
+    /// The password is too weak for the security policy (`SASL_WEAKPASS`).
+    WeakPassword,
+    /// Changing the password requires the old password
+    /// (`SASL_NEED_OLD_PASSWD`).
+    NeedOldPassword,
+    /// The change violates a constraint of the user store, e.g., a
+    /// uniqueness constraint (`SASL_CONSTRAINT_VIOLAT`).
+    ConstraintViolation,
+    /// The user store is unavailable (`SASL_UNAVAIL`).
+    Unavailable,
+}
+
+impl SetPasswordFailure {
+    pub(crate) fn code(self) -> c_int {
+        match self {
+            SetPasswordFailure::NoUser => SASL_NOUSER,
+            SetPasswordFailure::Disabled => SASL_DISABLED,
+            SetPasswordFailure::PasswordLocked => SASL_PWLOCK,
+            SetPasswordFailure::WeakPassword => SASL_WEAKPASS,
+            SetPasswordFailure::NeedOldPassword => SASL_NEED_OLD_PASSWD,
+            SetPasswordFailure::ConstraintViolation => SASL_CONSTRAINT_VIOLAT,
+            SetPasswordFailure::Unavailable => SASL_UNAVAIL,
+        }
+    }
+}
+
+bitflags! {
+    /// Options for [`SaslServer::set_password`](crate::server::SaslServer::set_password).
+    #[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+    pub struct SetPasswordFlags: c_uint {
+        /// Creates the user if it does not exist (`SASL_SET_CREATE`).
+        const CREATE = SASL_SET_CREATE;
+        /// Disables the user rather than setting a password
+        /// (`SASL_SET_DISABLE`).
+        const DISABLE = SASL_SET_DISABLE;
+        /// Does not store the plaintext password, only the verifiers derived
+        /// from it by mechanisms like SCRAM (`SASL_SET_NOPLAIN`).
+        const NO_PLAIN = SASL_SET_NOPLAIN;
+        /// Only sets the verifier of the mechanism used by the connection
+        /// (`SASL_SET_CURMECH_ONLY`).
+        const CURRENT_MECHANISM_ONLY = SASL_SET_CURMECH_ONLY;
+    }
+}
+
+/// Verifies plaintext passwords against an application's user store.
+///
+/// If the verifier rejects a password, libsasl2 falls back to the methods
+/// configured with `pwcheck_method`, which default to `auxprop`. If those
+/// reject it too, the exchange fails with the error corresponding to the
+/// [`AuthFailure`], e.g., [`SaslError::BadAuth`] for
+/// [`AuthFailure::BadPassword`]. This requires that the verifier be
+/// registered with the connection; for a verifier registered with a
+/// [`SaslContext`], the exchange fails with the result of the fallback
+/// methods instead.
+///
+/// [`SaslError::BadAuth`]: crate::error::SaslError::BadAuth
+/// [`SaslContext`]: crate::context::SaslContext
+pub trait PasswordVerifier: Send + Sync {
+    /// Checks `password` for the canonicalized `user`.
+    ///
+    /// The verifier may populate requested auxiliary properties of the user
+    /// in `props`. Passwords should be compared with [`constant_time_eq`].
+    fn check(
+        &self,
+        conn: &ConnectionInfo,
+        user: &str,
+        password: &[u8],
+        props: &mut AuxProps,
+    ) -> Result<(), AuthFailure>;
+}
+
+/// Stores passwords in an application's user store.
+///
+/// libsasl2 also hands the password to each mechanism that derives its own
+/// verifier from it, like SCRAM, and to any auxiliary property plugin that
+/// can store properties. If any of them fails, so does
+/// [`SaslServer::set_password`](crate::server::SaslServer::set_password).
+pub trait PasswordStore: Send + Sync {
+    /// Sets the password of `user`, or disables the user if `password` is
+    /// `None`.
+    ///
+    /// `flags` are the flags passed to `set_password`. The store may
+    /// populate requested auxiliary properties of the user in `props`.
+    fn set(
+        &self,
+        conn: &ConnectionInfo,
+        user: &str,
+        password: Option<&[u8]>,
+        props: &mut AuxProps,
+        flags: SetPasswordFlags,
+    ) -> Result<(), SetPasswordFailure>;
+}
+
+/// Compares two byte strings in time that depends only on their lengths,
+/// not their contents.
+pub fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+    let diff = a.iter().zip(b).fold(0, |acc, (x, y)| acc | (x ^ y));
+    // Keep the compiler from short-circuiting the fold.
+    std::hint::black_box(diff) == 0
+}
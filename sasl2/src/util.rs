@@ -0,0 +1,158 @@
+// Copyright Materialize, Inc. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License in the LICENSE file at the
+// root of this repository, or online at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Helpers for moving data across the FFI boundary.
+
+use std::convert::TryFrom;
+use std::env;
+use std::ffi::{CStr, CString, OsStr};
+use std::io::IoSlice;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::slice;
+
+use libc::{c_char, c_int, c_uint, c_void};
+use sasl2_sys::sasl::{iovec, sasl_conn_t, sasl_getprop, SASL_BADPARAM, SASL_OK};
+
+use crate::error::SaslError;
+
+/// Converts a Rust string into a C string, failing if it contains an interior
+/// nul byte.
+pub(crate) fn to_cstring(s: &str) -> Result<CString, SaslError> {
+    CString::new(s).map_err(|_| SaslError::nul())
+}
+
+/// Returns the pointer for an optional C string, or null if absent.
+pub(crate) fn opt_ptr(s: &Option<CString>) -> *const c_char {
+    s.as_ref().map_or(std::ptr::null(), |s| s.as_ptr())
+}
+
+/// Joins directories into the search path form that libsasl2 expects, i.e.,
+/// separated by `:`, or `;` on Windows.
+pub(crate) fn join_paths<I, P>(paths: I) -> Result<CString, SaslError>
+where
+    I: IntoIterator<Item = P>,
+    P: AsRef<Path>,
+{
+    let paths = env::join_paths(paths.into_iter().map(|p| p.as_ref().to_owned()))
+        .map_err(|_| SaslError::bad_param("path contains a separator"))?;
+    os_to_cstring(&paths)
+}
+
+fn os_to_cstring(s: &OsStr) -> Result<CString, SaslError> {
+    #[cfg(unix)]
+    let bytes = std::os::unix::ffi::OsStrExt::as_bytes(s).to_vec();
+    #[cfg(not(unix))]
+    let bytes = s
+        .to_str()
+        .ok_or_else(|| SaslError::bad_param("path is not valid Unicode"))?
+        .as_bytes()
+        .to_vec();
+    CString::new(bytes).map_err(|_| SaslError::nul())
+}
+
+/// Converts a path owned by libsasl2 into a Rust path.
+///
+/// # Safety
+///
+/// `s` must point to a valid nul-terminated string.
+pub(crate) unsafe fn to_path(s: *const c_char) -> PathBuf {
+    let bytes = CStr::from_ptr(s).to_bytes();
+    #[cfg(unix)]
+    let s = <OsStr as std::os::unix::ffi::OsStrExt>::from_bytes(bytes).to_owned();
+    #[cfg(not(unix))]
+    let s = std::ffi::OsString::from(String::from_utf8_lossy(bytes).into_owned());
+    PathBuf::from(s)
+}
+
+/// Formats a socket address in the `ip;port` form that libsasl2 expects.
+pub(crate) fn format_addr(addr: SocketAddr) -> CString {
+    CString::new(format!("{};{}", addr.ip(), addr.port())).unwrap()
+}
+
+/// Returns the length of a buffer as a `c_uint`, failing if the buffer is too
+/// large to pass to libsasl2.
+pub(crate) fn buf_len(buf: &[u8]) -> Result<c_uint, SaslError> {
+    c_uint::try_from(buf.len()).map_err(|_| SaslError::from_code(SASL_BADPARAM))
+}
+
+/// Converts an `IoSlice` into the `iovec` that libsasl2 expects.
+///
+/// The `iovec` borrows the slice's data, and must not outlive it.
+pub(crate) fn to_iovec(buf: &IoSlice) -> Result<iovec, SaslError> {
+    #[cfg(unix)]
+    let vec = iovec {
+        iov_base: buf.as_ptr() as *mut c_void,
+        iov_len: buf.len(),
+    };
+    #[cfg(windows)]
+    let vec = iovec {
+        iov_len: libc::c_long::try_from(buf.len())
+            .map_err(|_| SaslError::from_code(SASL_BADPARAM))?,
+        iov_base: buf.as_ptr() as *mut c_char,
+    };
+    Ok(vec)
+}
+
+/// Copies a buffer owned by libsasl2 into a Rust-owned vector.
+///
+/// # Safety
+///
+/// `data` must either be null or point to at least `len` readable bytes.
+pub(crate) unsafe fn copy_buf(data: *const c_char, len: c_uint) -> Vec<u8> {
+    if data.is_null() || len == 0 {
+        Vec::new()
+    } else {
+        slice::from_raw_parts(data as *const u8, len as usize).to_vec()
+    }
+}
+
+/// Copies a nul-terminated string owned by libsasl2 into a Rust string,
+/// returning `None` if the pointer is null.
+///
+/// # Safety
+///
+/// `s` must either be null or point to a valid nul-terminated string.
+pub(crate) unsafe fn copy_str(s: *const c_char) -> Option<String> {
+    if s.is_null() {
+        None
+    } else {
+        Some(CStr::from_ptr(s).to_string_lossy().into_owned())
+    }
+}
+
+/// Reads a property from a connection, returning `None` if the property is
+/// not available.
+///
+/// # Safety
+///
+/// `conn` must be a valid connection.
+pub(crate) unsafe fn getprop(conn: *mut sasl_conn_t, prop: c_uint) -> Option<*const c_void> {
+    let mut value = std::ptr::null();
+    match sasl_getprop(conn, prop as c_int, &mut value) {
+        SASL_OK if !value.is_null() => Some(value),
+        _ => None,
+    }
+}
+
+/// Reads a string-valued property from a connection.
+///
+/// # Safety
+///
+/// `conn` must be a valid connection, and `prop` must identify a property
+/// whose value is a nul-terminated string.
+pub(crate) unsafe fn getprop_str(conn: *mut sasl_conn_t, prop: c_uint) -> Option<String> {
+    getprop(conn, prop).and_then(|value| copy_str(value as *const c_char))
+}
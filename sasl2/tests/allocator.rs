@@ -0,0 +1,103 @@
+// Copyright Materialize, Inc. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License in the LICENSE file at the
+// root of this repository, or online at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use sasl2::allocator::RustAllocator;
+use sasl2::error::SaslError;
+use sasl2::server::SaslServer;
+
+mod common;
+
+/// Counts the calls that the installed allocator makes on behalf of
+/// libsasl2, which are recognizable by their 16-byte alignment.
+struct Counting;
+
+static MALLOCS: AtomicUsize = AtomicUsize::new(0);
+static REALLOCS: AtomicUsize = AtomicUsize::new(0);
+static FREES: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for Counting {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        if layout.align() == 16 {
+            MALLOCS.fetch_add(1, Ordering::SeqCst);
+        }
+        System.alloc(layout)
+    }
+
+    unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+        if layout.align() == 16 {
+            MALLOCS.fetch_add(1, Ordering::SeqCst);
+        }
+        System.alloc_zeroed(layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        if layout.align() == 16 {
+            REALLOCS.fetch_add(1, Ordering::SeqCst);
+        }
+        System.realloc(ptr, layout, new_size)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        if layout.align() == 16 {
+            FREES.fetch_add(1, Ordering::SeqCst);
+        }
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: Counting = Counting;
+
+fn counts() -> (usize, usize, usize) {
+    (
+        MALLOCS.load(Ordering::SeqCst),
+        REALLOCS.load(Ordering::SeqCst),
+        FREES.load(Ordering::SeqCst),
+    )
+}
+
+// The allocator is process-global, so everything is exercised from a single
+// test.
+#[test]
+fn test_allocator() {
+    RustAllocator::new().install().unwrap();
+    let err = RustAllocator::new().install().unwrap_err();
+    assert!(matches!(err, SaslError::BadParam(_)), "{:?}", err);
+
+    // libsasl2 keeps a few allocations for the life of the process once it
+    // has been initialized, so counting starts after a first exchange.
+    common::authenticate();
+    let (mallocs, reallocs, frees) = counts();
+    assert!(mallocs > 0);
+
+    for _ in 0..2 {
+        let (_client, server) = common::authenticate();
+        assert_eq!(server.username().as_deref(), Some("alice"));
+        // An error message that outgrows libsasl2's initial buffer is
+        // reallocated.
+        let mut server = SaslServer::builder("test").build().unwrap();
+        let err = server.start(&"X".repeat(200), None).unwrap_err();
+        assert!(matches!(err, SaslError::NoMech(_)), "{:?}", err);
+    }
+
+    // Everything allocated since was freed when the library was torn down.
+    let counts = counts();
+    assert!(counts.0 > mallocs);
+    assert!(counts.1 > reallocs);
+    assert_eq!(counts.0 - mallocs, counts.2 - frees);
+}
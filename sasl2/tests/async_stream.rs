@@ -0,0 +1,40 @@
+// Copyright Materialize, Inc. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License in the LICENSE file at the
+// root of this repository, or online at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use sasl2::async_stream::AsyncSaslStream;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+mod common;
+
+#[tokio::test]
+async fn test_async_stream_passthrough() {
+    let (client, server) = common::authenticate();
+
+    // A small pipe forces data to be delivered in pieces.
+    let (client_io, server_io) = tokio::io::duplex(3);
+    let mut client = AsyncSaslStream::new(client, client_io).unwrap();
+    let mut server = AsyncSaslStream::new(server, server_io).unwrap();
+    assert!(!client.is_protected());
+    assert!(!server.is_protected());
+
+    let writer = tokio::spawn(async move {
+        client.write_all(b"hello, world").await.unwrap();
+        client.shutdown().await.unwrap();
+    });
+    let mut buf = String::new();
+    server.read_to_string(&mut buf).await.unwrap();
+    assert_eq!(buf, "hello, world");
+    writer.await.unwrap();
+}
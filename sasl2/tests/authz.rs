@@ -0,0 +1,83 @@
+// Copyright Materialize, Inc. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License in the LICENSE file at the
+// root of this repository, or online at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use sasl2::authz::{AuthorizationPolicy, Decision, RuleTable};
+use sasl2::auxprop::AuxProps;
+use sasl2::callbacks::{Callbacks, ConnectionInfo};
+use sasl2::connection::Connection;
+use sasl2::error::SaslError;
+use sasl2::property::AuthExternal;
+use sasl2::server::{SaslServer, Step};
+
+#[test]
+fn test_rule_table_permits() {
+    let rules = RuleTable::new()
+        .allow("admin@*", "*")
+        .allow("ops-?", "*@tenant-a")
+        .allow("*-svc", "*-svc");
+    assert!(rules.permits("alice", "alice"));
+    assert!(!rules.permits("alice", "bob"));
+    assert!(rules.permits("admin@example.com", "bob"));
+    assert!(!rules.permits("admin", "bob"));
+    assert!(rules.permits("ops-1", "bob@tenant-a"));
+    assert!(!rules.permits("ops-12", "bob@tenant-a"));
+    assert!(!rules.permits("ops-1", "bob@tenant-b"));
+    assert!(rules.permits("web-svc", "db-svc"));
+    assert!(rules.permits("a-svc-svc", "-svc"));
+    assert!(!rules.permits("web-svc", "db-svc2"));
+}
+
+struct DenyAll;
+
+impl AuthorizationPolicy for DenyAll {
+    fn authorize(
+        &self,
+        conn: &ConnectionInfo,
+        requested_user: &str,
+        auth_identity: &str,
+        _default_realm: Option<&str>,
+        _props: &mut AuxProps,
+    ) -> Decision {
+        assert_eq!(conn.mechanism().as_deref(), Some("EXTERNAL"));
+        Decision::Deny(format!(
+            "{} may not act as {}",
+            auth_identity, requested_user
+        ))
+    }
+}
+
+fn start_external<P>(policy: P) -> Result<Step, SaslError>
+where
+    P: AuthorizationPolicy + 'static,
+{
+    let mut server = SaslServer::builder("test")
+        .callbacks(Callbacks::new().authorization_policy(policy))
+        .build()
+        .unwrap();
+    server.set_property::<AuthExternal>("alice").unwrap();
+    // Requesting a different authorization identity requires an auxiliary
+    // property plugin to look it up, and none is built, so only the
+    // authentication identity is exercised here.
+    server.start("EXTERNAL", Some(b""))
+}
+
+#[test]
+fn test_authorization_policy() {
+    assert_eq!(start_external(RuleTable::new()).unwrap(), Step::Done(None));
+
+    let err = start_external(DenyAll).unwrap_err();
+    assert!(matches!(err, SaslError::NoAuthz(_)), "{:?}", err);
+    assert!(err.detail().unwrap().contains("alice may not act as alice"));
+}
@@ -0,0 +1,134 @@
+// Copyright Materialize, Inc. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License in the LICENSE file at the
+// root of this repository, or online at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+
+use sasl2::callbacks::Callbacks;
+use sasl2::connection::Connection;
+use sasl2::error::SaslError;
+use sasl2::property::AuthExternal;
+use sasl2::server::{SaslServer, Step};
+use sasl2_sys::sasl::SASL_CU_AUTHID;
+
+fn external_server(callbacks: Callbacks) -> SaslServer {
+    let mut server = SaslServer::builder("test")
+        .success_data(true)
+        .callbacks(callbacks)
+        .build()
+        .unwrap();
+    server.set_property::<AuthExternal>("alice").unwrap();
+    server
+}
+
+#[test]
+fn test_proxy_policy() {
+    let requests = Arc::new(Mutex::new(vec![]));
+    let allow = Arc::new(AtomicBool::new(true));
+    let policy = {
+        let requests = Arc::clone(&requests);
+        let allow = Arc::clone(&allow);
+        move |requested: &str, authenticated: &str, _realm: Option<&str>| {
+            requests
+                .lock()
+                .unwrap()
+                .push((requested.to_owned(), authenticated.to_owned()));
+            if allow.load(Ordering::SeqCst) {
+                Ok(())
+            } else {
+                Err(format!("{} may not log in", authenticated))
+            }
+        }
+    };
+
+    // libsasl2 consults the proxy policy at the end of every successful
+    // exchange, even when no separate authorization identity is requested.
+    let mut server = external_server(Callbacks::new().proxy_policy(policy.clone()));
+    assert_eq!(
+        server.start("EXTERNAL", Some(b"")).unwrap(),
+        Step::Done(None)
+    );
+    assert_eq!(server.username().as_deref(), Some("alice"));
+
+    allow.store(false, Ordering::SeqCst);
+    let mut server = external_server(Callbacks::new().proxy_policy(policy));
+    let err = server.start("EXTERNAL", Some(b"")).unwrap_err();
+    assert!(matches!(err, SaslError::NoAuthz(_)), "{:?}", err);
+    assert!(err.detail().unwrap().contains("alice may not log in"));
+
+    assert_eq!(
+        *requests.lock().unwrap(),
+        vec![
+            ("alice".to_owned(), "alice".to_owned()),
+            ("alice".to_owned(), "alice".to_owned()),
+        ]
+    );
+}
+
+#[test]
+fn test_canon_user() {
+    let callbacks = Callbacks::new().canon_user(|user, _realm, flags| {
+        if flags & SASL_CU_AUTHID != 0 {
+            Some(user.to_uppercase())
+        } else {
+            Some(user.to_owned())
+        }
+    });
+    let mut server = external_server(callbacks);
+    assert_eq!(
+        server.start("EXTERNAL", Some(b"")).unwrap(),
+        Step::Done(None)
+    );
+    assert_eq!(server.auth_user().as_deref(), Some("ALICE"));
+}
+
+#[test]
+fn test_get_opt() {
+    let callbacks = Callbacks::new().get_opt(|plugin, option| match (plugin, option) {
+        (None, "canon_user_plugin") => Some("bogus".into()),
+        _ => None,
+    });
+    let mut server = external_server(callbacks);
+    let err = server.start("EXTERNAL", Some(b"")).unwrap_err();
+    assert!(matches!(err, SaslError::NoMech(_)), "{:?}", err);
+    assert!(err.detail().unwrap().contains("bogus"));
+}
+
+#[test]
+fn test_panic() {
+    let callbacks = Callbacks::new().proxy_policy(|_, _, _| panic!("boom"));
+    let mut server = external_server(callbacks);
+    let err = server.start("EXTERNAL", Some(b"")).unwrap_err();
+    assert!(matches!(err, SaslError::Fail(_)), "{:?}", err);
+}
+
+#[test]
+#[cfg(feature = "plain")]
+fn test_plain_client() {
+    use sasl2::client::{self, SaslClient};
+
+    let callbacks = Callbacks::new()
+        .user(|| None)
+        .authname(|| Some("alice".into()))
+        .pass(|| Some(b"secret".to_vec()));
+    let mut client = SaslClient::builder("test", "localhost")
+        .callbacks(callbacks)
+        .build()
+        .unwrap();
+    assert_eq!(
+        client.start("PLAIN").unwrap(),
+        client::Step::Done(Some(b"\0alice\0secret".to_vec()))
+    );
+}
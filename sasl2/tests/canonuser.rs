@@ -0,0 +1,110 @@
+// Copyright Materialize, Inc. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License in the LICENSE file at the
+// root of this repository, or online at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::sync::{Arc, Mutex};
+
+use sasl2::callbacks::{Callbacks, ConnectionInfo};
+use sasl2::canonuser::{CanonUserFlags, UserCanonicalizer};
+use sasl2::connection::Connection;
+use sasl2::error::SaslError;
+use sasl2::property::AuthExternal;
+use sasl2::server::{SaslServer, Step};
+
+/// Strips the realm from Kerberos principals and lower-cases the rest.
+#[derive(Clone, Default)]
+struct Normalize {
+    calls: Arc<Mutex<Vec<(String, CanonUserFlags)>>>,
+}
+
+impl UserCanonicalizer for Normalize {
+    fn canonicalize(
+        &self,
+        _conn: &ConnectionInfo,
+        user: &str,
+        _realm: Option<&str>,
+        flags: CanonUserFlags,
+    ) -> Option<String> {
+        self.calls.lock().unwrap().push((user.into(), flags));
+        match user {
+            "mallory" => None,
+            "long" => Some("x".repeat(4096)),
+            _ => Some(user.split('@').next().unwrap().to_lowercase()),
+        }
+    }
+}
+
+fn start_external(canonicalizer: Normalize, user: &str) -> (SaslServer, Result<Step, SaslError>) {
+    let mut server = SaslServer::builder("test")
+        .callbacks(Callbacks::new().user_canonicalizer(canonicalizer))
+        .build()
+        .unwrap();
+    server.set_property::<AuthExternal>(user).unwrap();
+    let res = server.start("EXTERNAL", Some(b""));
+    (server, res)
+}
+
+#[test]
+fn test_user_canonicalizer() {
+    let canonicalizer = Normalize::default();
+    let (server, res) = start_external(canonicalizer.clone(), "Alice@EXAMPLE.COM");
+    assert_eq!(res.unwrap(), Step::Done(None));
+    assert_eq!(server.auth_user().as_deref(), Some("alice"));
+    assert_eq!(server.username().as_deref(), Some("alice"));
+    assert_eq!(
+        *canonicalizer.calls.lock().unwrap(),
+        vec![(
+            "Alice@EXAMPLE.COM".to_owned(),
+            CanonUserFlags::AUTHID | CanonUserFlags::AUTHZID | CanonUserFlags::EXTERNALLY_VERIFIED
+        )]
+    );
+
+    let (_, res) = start_external(Normalize::default(), "mallory");
+    let err = res.unwrap_err();
+    assert!(matches!(err, SaslError::NoUser(_)), "{:?}", err);
+
+    let (_, res) = start_external(Normalize::default(), "long");
+    let err = res.unwrap_err();
+    assert!(matches!(err, SaslError::BufOver(_)), "{:?}", err);
+    assert!(err.detail().unwrap().contains("exceeds"), "{:?}", err);
+}
+
+#[test]
+#[cfg(feature = "plain")]
+fn test_user_canonicalizer_plain() {
+    use sasl2::client::{self, SaslClient};
+
+    let callbacks = Callbacks::new()
+        .user(|| None)
+        .authname(|| Some("Alice@EXAMPLE.COM".into()))
+        .pass(|| Some(b"secret".to_vec()))
+        .user_canonicalizer(Normalize::default());
+    let mut client = SaslClient::builder("test", "localhost")
+        .callbacks(callbacks)
+        .build()
+        .unwrap();
+    assert_eq!(
+        client.start("PLAIN").unwrap(),
+        client::Step::Done(Some(b"\0alice\0secret".to_vec()))
+    );
+
+    let mut server = SaslServer::builder("test")
+        .callbacks(Callbacks::new().user_canonicalizer(Normalize::default()))
+        .build()
+        .unwrap();
+    let err = server
+        .start("PLAIN", Some(b"\0\xffalice\0secret"))
+        .unwrap_err();
+    assert!(matches!(err, SaslError::BadProt(_)), "{:?}", err);
+}
@@ -0,0 +1,150 @@
+// Copyright Materialize, Inc. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License in the LICENSE file at the
+// root of this repository, or online at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use sasl2::channel_binding::ChannelBinding;
+use sasl2::connection::Connection;
+use sasl2::error::SaslError;
+use sasl2::property::{self, AuthExternal};
+use sasl2::server::{SaslServer, Step};
+use sasl2::session::ChannelBindingDisposition;
+
+/// A self-signed certificate signed with ECDSA and SHA-384.
+const CERT: &[u8] = include_bytes!("data/server.der");
+
+#[test]
+fn test_tls_server_end_point() {
+    let binding = ChannelBinding::tls_server_end_point(CERT).unwrap();
+    assert_eq!(binding.name(), "tls-server-end-point");
+    assert!(!binding.is_critical());
+    assert_eq!(
+        binding.data(),
+        &[
+            0x47, 0xe3, 0x75, 0xce, 0x1e, 0x21, 0xbb, 0x8e, 0x45, 0x99, 0x0e, 0xa2, 0x1d, 0x08,
+            0x05, 0x99, 0xe9, 0x5b, 0xea, 0xd3, 0xbe, 0xfa, 0xa8, 0x3a, 0xc4, 0x51, 0x2b, 0x3c,
+            0x83, 0xb9, 0xee, 0x0c, 0x5c, 0x98, 0xfd, 0xa9, 0x85, 0xdd, 0x69, 0xdd, 0xe3, 0xd6,
+            0xc4, 0xd4, 0x2a, 0x22, 0x04, 0x1e,
+        ][..]
+    );
+
+    for cert in [
+        &b""[..],
+        &CERT[..CERT.len() / 2],
+        b"\x30\x84\xff\xff\xff\xff",
+    ] {
+        let err = ChannelBinding::tls_server_end_point(cert).unwrap_err();
+        assert!(matches!(err, SaslError::BadParam(_)), "{:?}", err);
+    }
+}
+
+#[test]
+fn test_channel_binding() {
+    let binding = ChannelBinding::tls_exporter(vec![1; 32]).critical(true);
+    assert_eq!(binding.name(), "tls-exporter");
+    assert!(binding.is_critical());
+    assert_eq!(binding.data(), &[1; 32][..]);
+    assert_eq!(ChannelBinding::tls_unique(vec![2; 12]).name(), "tls-unique");
+
+    // EXTERNAL does not support channel binding, so a non-critical binding
+    // is ignored...
+    let mut server = SaslServer::builder("test")
+        .channel_binding(ChannelBinding::tls_unique(vec![2; 12]))
+        .build()
+        .unwrap();
+    server.set_property::<AuthExternal>("alice").unwrap();
+    assert_eq!(
+        server.start("EXTERNAL", Some(b"")).unwrap(),
+        Step::Done(None)
+    );
+    let info = server.session_info().unwrap();
+    assert_eq!(info.channel_binding(), ChannelBindingDisposition::Absent);
+
+    // ...but a critical binding is enforced.
+    let mut server = SaslServer::builder("test").build().unwrap();
+    server
+        .set_property::<property::ChannelBinding>(&binding)
+        .unwrap();
+    server.set_property::<AuthExternal>("alice").unwrap();
+    let err = server.start("EXTERNAL", Some(b"")).unwrap_err();
+    assert!(matches!(err, SaslError::BadBinding(_)), "{:?}", err);
+
+    let binding = ChannelBinding::new("a\0b", vec![]);
+    let err = server
+        .set_property::<property::ChannelBinding>(&binding)
+        .unwrap_err();
+    assert!(matches!(err, SaslError::BadParam(_)), "{:?}", err);
+}
+
+#[test]
+#[cfg(feature = "plain")]
+fn test_client_critical() {
+    use sasl2::client::SaslClient;
+
+    // A client with a critical binding refuses mechanisms that do not
+    // support channel binding.
+    let binding = ChannelBinding::tls_exporter(vec![1; 32]).critical(true);
+    let mut client = SaslClient::builder("test", "localhost")
+        .channel_binding(binding)
+        .build()
+        .unwrap();
+    let err = client.start("PLAIN").unwrap_err();
+    assert!(matches!(err, SaslError::NoMech(_)), "{:?}", err);
+}
+
+#[test]
+#[cfg(feature = "scram")]
+fn test_client_mechanism_policy_binding() {
+    use sasl2::client::{self, SaslClient};
+    use sasl2::mechanism::MechanismPolicy;
+    use sasl2_sys::sasl::{SASL_CB_AUTHNAME, SASL_CB_PASS};
+
+    // Returns the GS2 header of the client's first SCRAM message.
+    let gs2_header = |offered: &str| -> String {
+        let policy = MechanismPolicy::new()
+            .allow("SCRAM-SHA-256-PLUS")
+            .allow("SCRAM-SHA-256");
+        let mut client = SaslClient::builder("test", "localhost")
+            .channel_binding(ChannelBinding::tls_exporter(vec![1; 32]))
+            .mechanism_policy(policy)
+            .build()
+            .unwrap();
+        let mut prompts = match client.start(offered).unwrap() {
+            client::Step::Interact(prompts) => prompts,
+            step => panic!("unexpected step: {:?}", step),
+        };
+        for prompt in &mut prompts {
+            match prompt.id() {
+                SASL_CB_AUTHNAME => prompt.answer("alice"),
+                SASL_CB_PASS => prompt.answer("secret"),
+                _ => (),
+            }
+        }
+        let data = match client.interact(prompts).unwrap() {
+            client::Step::Continue(data) => data,
+            step => panic!("unexpected step: {:?}", step),
+        };
+        let data = String::from_utf8(data).unwrap();
+        data.splitn(3, ',').take(2).collect::<Vec<_>>().join(",")
+    };
+
+    // A client that supports channel binding tells a server that does not
+    // appear to, so that the server can detect a downgrade...
+    assert_eq!(gs2_header("SCRAM-SHA-256"), "y,");
+    assert_eq!(gs2_header("PLAIN SCRAM-SHA-256"), "y,");
+    // ...and binds the channel when the server does.
+    assert_eq!(
+        gs2_header("SCRAM-SHA-256 SCRAM-SHA-256-PLUS"),
+        "p=tls-exporter,"
+    );
+}
@@ -0,0 +1,217 @@
+// Copyright Materialize, Inc. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License in the LICENSE file at the
+// root of this repository, or online at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use sasl2::client::{SaslClient, Step};
+use sasl2::connection::Connection;
+use sasl2::error::SaslError;
+use sasl2::property::AuthExternal;
+use sasl2_sys::sasl::SASL_CB_USER;
+
+#[test]
+fn test_client_external() {
+    let mut client = SaslClient::builder("test", "localhost")
+        .local_addr("127.0.0.1:1234".parse().unwrap())
+        .remote_addr("127.0.0.1:5678".parse().unwrap())
+        .build()
+        .unwrap();
+
+    let err = client.start("EXTERNAL").unwrap_err();
+    assert!(matches!(err, SaslError::NoMech(_)), "{:?}", err);
+
+    client.set_property::<AuthExternal>("alice").unwrap();
+
+    // EXTERNAL asks for an optional authorization identity.
+    let mut prompts = match client.start("EXTERNAL").unwrap() {
+        Step::Interact(prompts) => prompts,
+        step => panic!("unexpected step: {:?}", step),
+    };
+    assert_eq!(client.mechanism().as_deref(), Some("EXTERNAL"));
+    let ids: Vec<_> = prompts.iter().map(|p| p.id()).collect();
+    assert_eq!(ids, vec![SASL_CB_USER]);
+
+    prompts.get_mut(SASL_CB_USER).unwrap().answer("bob");
+    assert_eq!(
+        client.interact(prompts).unwrap(),
+        Step::Done(Some(b"bob".to_vec()))
+    );
+}
+
+#[test]
+#[cfg(feature = "plain")]
+fn test_client_plain_interact() {
+    use sasl2_sys::sasl::{SASL_CB_AUTHNAME, SASL_CB_PASS};
+
+    let mut client = SaslClient::builder("test", "localhost").build().unwrap();
+    let mut prompts = match client.start("PLAIN").unwrap() {
+        Step::Interact(prompts) => prompts,
+        step => panic!("unexpected step: {:?}", step),
+    };
+    for prompt in &mut prompts {
+        match prompt.id() {
+            SASL_CB_AUTHNAME => prompt.answer("alice"),
+            SASL_CB_PASS => prompt.answer("secret"),
+            _ => (),
+        }
+    }
+
+    // Prompts from an earlier round are rejected.
+    let stale = prompts.clone();
+    assert_eq!(
+        client.interact(prompts).unwrap(),
+        Step::Done(Some(b"\0alice\0secret".to_vec()))
+    );
+    let err = client.interact(stale).unwrap_err();
+    assert!(matches!(err, SaslError::BadParam(_)), "{:?}", err);
+}
+
+#[test]
+fn test_client_foreign_prompts() {
+    let external = || {
+        let mut client = SaslClient::builder("test", "localhost").build().unwrap();
+        client.set_property::<AuthExternal>("alice").unwrap();
+        let prompts = match client.start("EXTERNAL").unwrap() {
+            Step::Interact(prompts) => prompts,
+            step => panic!("unexpected step: {:?}", step),
+        };
+        (client, prompts)
+    };
+    let (_, a) = external();
+    let (mut b, prompts) = external();
+
+    // The prompts of another client at the same round are rejected, and the
+    // exchange remains paused.
+    let err = b.interact(a).unwrap_err();
+    assert!(matches!(err, SaslError::BadParam(_)), "{:?}", err);
+    assert_eq!(b.interact(prompts).unwrap(), Step::Done(Some(vec![])));
+}
+
+#[test]
+#[cfg(feature = "plain")]
+fn test_client_foreign_prompts_plain() {
+    let mut plain = SaslClient::builder("test", "localhost").build().unwrap();
+    let prompts = match plain.start("PLAIN").unwrap() {
+        Step::Interact(prompts) => prompts,
+        step => panic!("unexpected step: {:?}", step),
+    };
+    assert!(prompts.len() > 1);
+
+    // More prompts than the paused exchange asked for are rejected rather
+    // than written past the end of its prompt array.
+    let mut external = SaslClient::builder("test", "localhost").build().unwrap();
+    external.set_property::<AuthExternal>("alice").unwrap();
+    assert!(matches!(
+        external.start("EXTERNAL").unwrap(),
+        Step::Interact(_)
+    ));
+    let err = external.interact(prompts).unwrap_err();
+    assert!(matches!(err, SaslError::BadParam(_)), "{:?}", err);
+}
+
+#[test]
+#[cfg(feature = "plain")]
+fn test_client_no_plaintext() {
+    use sasl2::security::{SecurityFlags, SecurityProperties};
+
+    let props = SecurityProperties::builder()
+        .flags(SecurityFlags::NO_PLAINTEXT)
+        .build();
+    let mut client = SaslClient::builder("test", "localhost")
+        .security_properties(props)
+        .build()
+        .unwrap();
+    let err = client.start("PLAIN").unwrap_err();
+    assert!(matches!(err, SaslError::NoMech(_)), "{:?}", err);
+}
+
+#[test]
+fn test_client_mechanism_policy() {
+    use sasl2::mechanism::{MechanismPolicy, SkipReason};
+
+    let policy = MechanismPolicy::new()
+        .allow("SCRAM-SHA-256-PLUS")
+        .allow("SCRAM-SHA-256")
+        .allow_over_tls("PLAIN")
+        .allow("EXTERNAL");
+    let offered = "CRAM-MD5 EXTERNAL,PLAIN SCRAM-SHA-256-PLUS";
+    let skipped = |client: &SaslClient| -> Vec<(String, SkipReason)> {
+        let selection = client.selection().unwrap();
+        selection
+            .skipped()
+            .iter()
+            .map(|s| (s.name().to_owned(), s.reason()))
+            .collect()
+    };
+
+    let mut client = SaslClient::builder("test", "localhost")
+        .mechanism_policy(policy.clone())
+        .build()
+        .unwrap();
+    client.set_property::<AuthExternal>("alice").unwrap();
+    assert!(matches!(client.start(offered).unwrap(), Step::Interact(_)));
+    assert_eq!(client.selection().unwrap().selected(), Some("EXTERNAL"));
+    assert_eq!(client.mechanism().as_deref(), Some("EXTERNAL"));
+    assert_eq!(
+        skipped(&client),
+        vec![
+            ("CRAM-MD5".to_owned(), SkipReason::NotAllowed),
+            ("PLAIN".to_owned(), SkipReason::RequiresTls),
+            (
+                "SCRAM-SHA-256-PLUS".to_owned(),
+                SkipReason::RequiresChannelBinding
+            ),
+        ]
+    );
+
+    // Without any acceptable mechanism, the policy refuses to start.
+    let mut client = SaslClient::builder("test", "localhost")
+        .mechanism_policy(policy)
+        .build()
+        .unwrap();
+    let err = client.start("CRAM-MD5 PLAIN").unwrap_err();
+    assert!(matches!(err, SaslError::NoMech(_)), "{:?}", err);
+    assert_eq!(
+        err.detail(),
+        Some("no acceptable mechanism; skipped CRAM-MD5 (not allowed), PLAIN (requires TLS)")
+    );
+    assert_eq!(client.selection().unwrap().selected(), None);
+    assert_eq!(skipped(&client).len(), 2);
+}
+
+#[test]
+#[cfg(feature = "plain")]
+fn test_client_mechanism_policy_tls() {
+    use sasl2::mechanism::{MechanismPolicy, SkipReason};
+
+    let policy = MechanismPolicy::new()
+        .allow_over_tls("PLAIN")
+        .allow("EXTERNAL");
+    let mut client = SaslClient::builder("test", "localhost")
+        .mechanism_policy(policy)
+        .tls(true)
+        .build()
+        .unwrap();
+    client.set_property::<AuthExternal>("alice").unwrap();
+    client.start("EXTERNAL PLAIN").unwrap();
+    assert_eq!(client.mechanism().as_deref(), Some("PLAIN"));
+    let selection = client.selection().unwrap();
+    assert_eq!(selection.selected(), Some("PLAIN"));
+    assert_eq!(selection.skipped()[0].name(), "EXTERNAL");
+    assert_eq!(selection.skipped()[0].reason(), SkipReason::Outranked);
+}
+
+#[test]
+fn test_client_nul() {
+    assert!(SaslClient::builder("te\0st", "localhost").build().is_err());
+}
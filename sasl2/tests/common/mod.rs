@@ -0,0 +1,42 @@
+// Copyright Materialize, Inc. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License in the LICENSE file at the
+// root of this repository, or online at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use sasl2::client::{self, SaslClient};
+use sasl2::connection::Connection;
+use sasl2::property::AuthExternal;
+use sasl2::server::{self, SaslServer};
+
+/// Authenticates a client and server with EXTERNAL, which never negotiates a
+/// security layer.
+pub fn authenticate() -> (SaslClient, SaslServer) {
+    let mut client = SaslClient::builder("test", "localhost").build().unwrap();
+    client.set_property::<AuthExternal>("alice").unwrap();
+    let prompts = match client.start("EXTERNAL").unwrap() {
+        client::Step::Interact(prompts) => prompts,
+        step => panic!("unexpected step: {:?}", step),
+    };
+    let client_data = match client.interact(prompts).unwrap() {
+        client::Step::Done(data) => data.unwrap_or_default(),
+        step => panic!("unexpected step: {:?}", step),
+    };
+
+    let mut server = SaslServer::builder("test").build().unwrap();
+    server.set_property::<AuthExternal>("alice").unwrap();
+    assert_eq!(
+        server.start("EXTERNAL", Some(&client_data)).unwrap(),
+        server::Step::Done(None)
+    );
+    (client, server)
+}
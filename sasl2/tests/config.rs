@@ -0,0 +1,101 @@
+// Copyright Materialize, Inc. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License in the LICENSE file at the
+// root of this repository, or online at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use sasl2::callbacks::Callbacks;
+use sasl2::config::SaslConfig;
+use sasl2::connection::Connection;
+use sasl2::error::SaslError;
+use sasl2::property::AuthExternal;
+use sasl2::server::{SaslServer, Step};
+
+#[test]
+fn test_config_scoping() {
+    let config = SaslConfig::new()
+        .mech_list(["SCRAM-SHA-256", "PLAIN"])
+        .scram_iteration_counter(8192)
+        .option("scram_iteration_counter", "1")
+        .option("log_level", "3")
+        .plugin_option("scram-sha-256", "scram_iteration_counter", "16384");
+    assert_eq!(
+        config.get(None, "mech_list").as_deref(),
+        Some("SCRAM-SHA-256 PLAIN")
+    );
+    assert_eq!(config.get(None, "log_level").as_deref(), Some("3"));
+    assert_eq!(
+        config.get(None, "scram_iteration_counter").as_deref(),
+        Some("8192")
+    );
+    assert_eq!(
+        config
+            .get(Some("SCRAM-SHA-256"), "scram_iteration_counter")
+            .as_deref(),
+        Some("16384")
+    );
+    assert_eq!(
+        config
+            .get(Some("SCRAM-SHA-1"), "scram_iteration_counter")
+            .as_deref(),
+        Some("8192")
+    );
+    assert_eq!(config.get(None, "pwcheck_method"), None);
+}
+
+#[test]
+fn test_config_mech_list() {
+    let server = |config: SaslConfig| {
+        let mut server = SaslServer::builder("test")
+            .callbacks(Callbacks::new().config(config))
+            .build()
+            .unwrap();
+        server.set_property::<AuthExternal>("alice").unwrap();
+        server.start("EXTERNAL", Some(b""))
+    };
+
+    assert_eq!(
+        server(SaslConfig::new().mech_list(["EXTERNAL"])).unwrap(),
+        Step::Done(None)
+    );
+    let err = server(SaslConfig::new().mech_list(["PLAIN"])).unwrap_err();
+    assert!(matches!(err, SaslError::NoMech(_)), "{:?}", err);
+}
+
+#[test]
+#[cfg(feature = "serde")]
+fn test_config_deserialize() {
+    let config: SaslConfig = toml::from_str(
+        r#"
+        mech_list = ["SCRAM-SHA-256", "PLAIN"]
+        pwcheck_method = ["auxprop"]
+
+        [options]
+        log_level = "3"
+
+        [plugins.SCRAM-SHA-256]
+        scram_iteration_counter = "16384"
+        "#,
+    )
+    .unwrap();
+    assert_eq!(
+        config,
+        SaslConfig::new()
+            .mech_list(["SCRAM-SHA-256", "PLAIN"])
+            .pwcheck_method(["auxprop"])
+            .option("log_level", "3")
+            .plugin_option("SCRAM-SHA-256", "scram_iteration_counter", "16384")
+    );
+
+    let err = toml::from_str::<SaslConfig>("mechs = []").unwrap_err();
+    assert!(err.to_string().contains("unknown field"), "{}", err);
+}
@@ -0,0 +1,93 @@
+// Copyright Materialize, Inc. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License in the LICENSE file at the
+// root of this repository, or online at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+use sasl2::allocator::RustAllocator;
+use sasl2::callbacks::Callbacks;
+use sasl2::connection::Connection;
+use sasl2::context::SaslContext;
+use sasl2::error::SaslError;
+use sasl2::property::AuthExternal;
+use sasl2::server::{SaslServer, Step};
+
+// The library state is process-global, so everything is exercised from a
+// single test.
+#[test]
+fn test_context() {
+    let calls = Arc::new(AtomicUsize::new(0));
+    let callbacks = Callbacks::new().proxy_policy({
+        let calls = Arc::clone(&calls);
+        move |_: &str, _: &str, _: Option<&str>| {
+            calls.fetch_add(1, Ordering::SeqCst);
+            Ok(())
+        }
+    });
+    let context = SaslContext::builder()
+        .callbacks(callbacks)
+        .server("test_context")
+        .unwrap();
+
+    // Further handles may join, but not reconfigure, the initialized
+    // library.
+    let other = SaslContext::server("test_context").unwrap();
+    let err = SaslContext::server("other").unwrap_err();
+    assert!(matches!(err, SaslError::BadParam(_)), "{:?}", err);
+    let err = SaslContext::builder()
+        .callbacks(Callbacks::new())
+        .server("test_context")
+        .unwrap_err();
+    assert!(matches!(err, SaslError::BadParam(_)), "{:?}", err);
+    drop(other);
+
+    // Hooks can only be installed before the library is initialized.
+    let err = RustAllocator::new().install().unwrap_err();
+    assert!(matches!(err, SaslError::BadParam(_)), "{:?}", err);
+
+    // Connections consult the global callbacks.
+    let mut server = SaslServer::builder("test").build().unwrap();
+    server.set_property::<AuthExternal>("alice").unwrap();
+    assert_eq!(
+        server.start("EXTERNAL", Some(b"")).unwrap(),
+        Step::Done(None)
+    );
+    assert_eq!(calls.load(Ordering::SeqCst), 1);
+
+    // The library remains initialized until both the context and the
+    // connection are dropped...
+    drop(context.clone());
+    drop(context);
+    let err = SaslContext::server("other").unwrap_err();
+    assert!(matches!(err, SaslError::BadParam(_)), "{:?}", err);
+
+    // ...after which it may be initialized afresh.
+    drop(server);
+    let _context = SaslContext::server("other").unwrap();
+    let mut server = SaslServer::builder("test").build().unwrap();
+    server.set_property::<AuthExternal>("alice").unwrap();
+    assert_eq!(
+        server.start("EXTERNAL", Some(b"")).unwrap(),
+        Step::Done(None)
+    );
+    assert_eq!(calls.load(Ordering::SeqCst), 1);
+
+    let _client = SaslContext::client().unwrap();
+    let err = SaslContext::builder()
+        .callbacks(Callbacks::new())
+        .client()
+        .unwrap_err();
+    assert!(matches!(err, SaslError::BadParam(_)), "{:?}", err);
+}
@@ -0,0 +1,56 @@
+// Copyright Materialize, Inc. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License in the LICENSE file at the
+// root of this repository, or online at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::ptr;
+
+use sasl2::error::{self, SaslError, Status};
+use sasl2_sys::sasl::{
+    SASL_BADAUTH, SASL_CONTINUE, SASL_INTERACT, SASL_NOUSER, SASL_OK, SASL_WEAKPASS,
+};
+
+#[test]
+fn test_check() {
+    unsafe {
+        assert_eq!(error::check(ptr::null_mut(), SASL_OK), Ok(Status::Ok));
+        assert_eq!(
+            error::check(ptr::null_mut(), SASL_CONTINUE),
+            Ok(Status::Continue)
+        );
+        assert_eq!(
+            error::check(ptr::null_mut(), SASL_INTERACT),
+            Ok(Status::Interact)
+        );
+        let err = error::check(ptr::null_mut(), SASL_BADAUTH).unwrap_err();
+        assert!(matches!(err, SaslError::BadAuth(_)));
+        assert_eq!(err.code(), SASL_BADAUTH);
+        assert_eq!(err.detail(), None);
+        assert_eq!(err.to_string(), err.description());
+    }
+}
+
+#[test]
+fn test_from_code() {
+    assert!(matches!(
+        SaslError::from_code(SASL_NOUSER),
+        SaslError::NoUser(_)
+    ));
+    assert!(matches!(
+        SaslError::from_code(SASL_WEAKPASS),
+        SaslError::WeakPass(_)
+    ));
+    let err = SaslError::from_code(-1000);
+    assert!(matches!(err, SaslError::Unknown(-1000, _)));
+    assert_eq!(err.code(), -1000);
+}
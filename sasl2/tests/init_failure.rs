@@ -0,0 +1,35 @@
+// Copyright Materialize, Inc. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License in the LICENSE file at the
+// root of this repository, or online at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use sasl2::allocator::RustAllocator;
+use sasl2::context::SaslContext;
+use sasl2::error::SaslError;
+
+// libsasl2 locks its allocation and locking functions before it validates
+// its arguments, so the hooks must be refused even after a failed
+// initialization. The hooks are process-global, so this runs in its own
+// test binary.
+#[test]
+fn test_install_after_failed_init() {
+    // Application names must fit in a path.
+    let appname = "a".repeat(8192);
+    let err = SaslContext::server(&appname).unwrap_err();
+    assert!(matches!(err, SaslError::BadParam(_)), "{:?}", err);
+
+    let err = sasl2::mutex::install().unwrap_err();
+    assert!(matches!(err, SaslError::BadParam(_)), "{:?}", err);
+    let err = RustAllocator::new().install().unwrap_err();
+    assert!(matches!(err, SaslError::BadParam(_)), "{:?}", err);
+}
@@ -0,0 +1,94 @@
+// Copyright Materialize, Inc. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License in the LICENSE file at the
+// root of this repository, or online at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+
+use log::kv::{self, Key, Value, VisitSource};
+use log::{Level, Log, Metadata, Record};
+use sasl2::callbacks::Callbacks;
+use sasl2::connection::Connection;
+use sasl2::error::SaslError;
+use sasl2::logging::LogForwarder;
+use sasl2::property::AuthExternal;
+use sasl2::server::SaslServer;
+
+struct Event {
+    level: Level,
+    target: String,
+    message: String,
+    fields: BTreeMap<String, String>,
+}
+
+struct Logger(Mutex<Vec<Event>>);
+
+impl Log for Logger {
+    fn enabled(&self, _: &Metadata) -> bool {
+        true
+    }
+
+    fn log(&self, record: &Record) {
+        struct Fields(BTreeMap<String, String>);
+
+        impl<'kvs> VisitSource<'kvs> for Fields {
+            fn visit_pair(&mut self, key: Key<'kvs>, value: Value<'kvs>) -> Result<(), kv::Error> {
+                self.0.insert(key.to_string(), value.to_string());
+                Ok(())
+            }
+        }
+
+        let mut fields = Fields(BTreeMap::new());
+        record.key_values().visit(&mut fields).unwrap();
+        self.0.lock().unwrap().push(Event {
+            level: record.level(),
+            target: record.target().into(),
+            message: record.args().to_string(),
+            fields: fields.0,
+        });
+    }
+
+    fn flush(&self) {}
+}
+
+static LOGGER: Logger = Logger(Mutex::new(vec![]));
+
+#[test]
+fn test_log_forwarder() {
+    log::set_logger(&LOGGER).unwrap();
+    log::set_max_level(log::LevelFilter::Trace);
+
+    let callbacks = Callbacks::new()
+        .log_with_source(LogForwarder::new().log())
+        .proxy_policy(|_, _, _| Err("denied by policy".into()));
+    let mut server = SaslServer::builder("test")
+        .remote_addr("192.0.2.1:5432".parse().unwrap())
+        .callbacks(callbacks)
+        .build()
+        .unwrap();
+    server.set_property::<AuthExternal>("alice").unwrap();
+    let err = server.start("EXTERNAL", Some(b"")).unwrap_err();
+    assert!(matches!(err, SaslError::NoAuthz(_)), "{:?}", err);
+
+    let events = LOGGER.0.lock().unwrap();
+    let event = events
+        .iter()
+        .find(|event| event.message.contains("denied by policy"))
+        .expect("denial was not logged");
+    assert_eq!(event.level, Level::Warn);
+    assert_eq!(event.target, "sasl2");
+    assert_eq!(event.fields["service"], "test");
+    assert_eq!(event.fields["mechanism"], "EXTERNAL");
+    assert_eq!(event.fields["remote"], "192.0.2.1;5432");
+}
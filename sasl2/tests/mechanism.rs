@@ -0,0 +1,83 @@
+// Copyright Materialize, Inc. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License in the LICENSE file at the
+// root of this repository, or online at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use sasl2::client::SaslClient;
+use sasl2::connection::Connection;
+use sasl2::mechanism::{self, MechanismFeatures};
+use sasl2::property::AuthExternal;
+use sasl2::security::{SecurityFlags, SecurityProperties};
+use sasl2::server::SaslServer;
+
+#[test]
+fn test_mechanisms() {
+    let mechs = mechanism::mechanisms().unwrap();
+    let external = mechs.iter().find(|m| m.name() == "EXTERNAL").unwrap();
+    assert!(external.client());
+    assert!(external.server());
+    assert_eq!(external.max_ssf(), 0);
+    assert!(external
+        .security_flags()
+        .contains(SecurityFlags::NO_PLAINTEXT));
+    assert!(external
+        .features()
+        .contains(MechanismFeatures::WANT_CLIENT_FIRST | MechanismFeatures::ALLOWS_PROXY));
+
+    #[cfg(feature = "plain")]
+    {
+        let plain = mechs.iter().find(|m| m.name() == "PLAIN").unwrap();
+        assert!(plain.client() && plain.server());
+        assert!(!plain.security_flags().contains(SecurityFlags::NO_PLAINTEXT));
+    }
+    #[cfg(feature = "scram")]
+    {
+        let scram = mechs.iter().find(|m| m.name() == "SCRAM-SHA-256").unwrap();
+        assert!(scram
+            .features()
+            .contains(MechanismFeatures::CHANNEL_BINDING));
+    }
+
+    let names = mechanism::server_mechanism_names().unwrap();
+    assert!(names.iter().any(|n| n == "EXTERNAL"));
+
+    // No auxiliary property plugins are built.
+    assert_eq!(mechanism::auxprop_plugins().unwrap(), Vec::<String>::new());
+}
+
+#[test]
+fn test_list_mechanisms() {
+    // EXTERNAL is only offered once the external identity is known.
+    let mut server = SaslServer::builder("test").build().unwrap();
+    let mechs = server.list_mechanisms(Some("alice")).unwrap();
+    assert!(!mechs.iter().any(|m| m == "EXTERNAL"), "{:?}", mechs);
+    server.set_property::<AuthExternal>("alice").unwrap();
+    let mechs = server.list_mechanisms(Some("alice")).unwrap();
+    assert!(mechs.iter().any(|m| m == "EXTERNAL"), "{:?}", mechs);
+    #[cfg(feature = "plain")]
+    assert!(mechs.iter().any(|m| m == "PLAIN"), "{:?}", mechs);
+
+    let client = SaslClient::builder("test", "localhost").build().unwrap();
+    let mechs = client.list_mechanisms(None).unwrap();
+    assert!(mechs.iter().any(|m| m == "EXTERNAL"), "{:?}", mechs);
+
+    // No mechanism provides forward secrecy.
+    let props = SecurityProperties::builder()
+        .flags(SecurityFlags::FORWARD_SECRECY)
+        .build();
+    let server = SaslServer::builder("test")
+        .security_properties(props)
+        .build()
+        .unwrap();
+    assert_eq!(server.list_mechanisms(None).unwrap(), Vec::<String>::new());
+}
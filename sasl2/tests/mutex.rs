@@ -0,0 +1,56 @@
+// Copyright Materialize, Inc. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License in the LICENSE file at the
+// root of this repository, or online at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::sync::Barrier;
+use std::thread;
+
+use sasl2::allocator::RustAllocator;
+use sasl2::error::SaslError;
+
+mod common;
+
+const THREADS: usize = 16;
+const EXCHANGES: usize = 100;
+
+// The hooks are process-global, so everything is exercised from a single
+// test.
+#[test]
+fn test_concurrent_exchanges() {
+    sasl2::mutex::install().unwrap();
+    let err = sasl2::mutex::install().unwrap_err();
+    assert!(matches!(err, SaslError::BadParam(_)), "{:?}", err);
+    RustAllocator::new().install().unwrap();
+
+    // Connections are created and dropped concurrently, so the library is
+    // also repeatedly initialized and torn down.
+    let barrier = Barrier::new(THREADS);
+    thread::scope(|s| {
+        for _ in 0..THREADS {
+            let barrier = &barrier;
+            s.spawn(move || {
+                barrier.wait();
+                for _ in 0..EXCHANGES {
+                    let (_client, server) = common::authenticate();
+                    assert_eq!(server.username().as_deref(), Some("alice"));
+                }
+            });
+        }
+    });
+
+    // libsasl2 took the locks, and released every one.
+    let (locks, unlocks) = sasl2::mutex::lock_counts();
+    assert!(locks > 0);
+    assert_eq!(locks, unlocks);
+}
@@ -0,0 +1,73 @@
+// Copyright Materialize, Inc. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License in the LICENSE file at the
+// root of this repository, or online at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::env;
+use std::fs;
+use std::sync::{Arc, Mutex};
+
+use sasl2::callbacks::{Callbacks, FileType};
+use sasl2::connection::Connection;
+use sasl2::context::{self, SaslContext};
+use sasl2::error::SaslError;
+use sasl2::property::AuthExternal;
+use sasl2::server::{SaslServer, Step};
+
+fn start_external() -> Result<Step, SaslError> {
+    let mut server = SaslServer::builder("test").build().unwrap();
+    server.set_property::<AuthExternal>("alice").unwrap();
+    server.start("EXTERNAL", Some(b""))
+}
+
+// Global state is only reset when the library is torn down, so this test
+// runs its steps sequentially.
+#[test]
+fn test_config_path() {
+    let dir = env::temp_dir().join(format!("sasl2-path-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(dir.join("pathtest.conf"), "mech_list: PLAIN\n").unwrap();
+    context::set_config_path([&dir]).unwrap();
+
+    // The configuration file disables EXTERNAL...
+    let context = SaslContext::server("pathtest").unwrap();
+    let err = start_external().unwrap_err();
+    assert!(matches!(err, SaslError::NoMech(_)), "{:?}", err);
+    drop(context);
+
+    // ...unless the application vetoes it. The path survives the teardown
+    // above.
+    let verified = Arc::new(Mutex::new(vec![]));
+    let callbacks = Callbacks::new().verify_file({
+        let verified = Arc::clone(&verified);
+        move |path, file_type| {
+            verified.lock().unwrap().push((path.to_owned(), file_type));
+            file_type != FileType::Config
+        }
+    });
+    let context = SaslContext::builder()
+        .callbacks(callbacks)
+        .server("pathtest")
+        .unwrap();
+    assert_eq!(start_external().unwrap(), Step::Done(None));
+    assert!(verified
+        .lock()
+        .unwrap()
+        .contains(&(dir.join("pathtest.conf"), FileType::Config)));
+    drop(context);
+
+    let err = context::set_config_path(["a\0b"]).unwrap_err();
+    assert!(matches!(err, SaslError::BadParam(_)), "{:?}", err);
+
+    fs::remove_dir_all(&dir).unwrap();
+}
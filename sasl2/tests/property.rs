@@ -0,0 +1,148 @@
+// Copyright Materialize, Inc. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License in the LICENSE file at the
+// root of this repository, or online at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::ffi::CStr;
+use std::slice;
+
+use libc::c_void;
+use sasl2::client::SaslClient;
+use sasl2::connection::Connection;
+use sasl2::error::SaslError;
+use sasl2::property::{
+    AuthExternal, DefUserRealm, GssCredentials, GssCreds, HttpRequest, HttpRequestData,
+    IpLocalPort, MechName, SecProps, ServerFqdn, Service, Ssf, SsfExternal,
+};
+use sasl2::security::{SecurityFlags, SecurityProperties};
+use sasl2::server::{SaslServer, Step};
+
+#[test]
+fn test_server_properties() {
+    let mut server = SaslServer::builder("test")
+        .server_fqdn("example.com")
+        .user_realm("EXAMPLE.COM")
+        .build()
+        .unwrap();
+    assert_eq!(server.get_property::<Service>().unwrap(), "test");
+    assert_eq!(server.get_property::<ServerFqdn>().unwrap(), "example.com");
+    assert_eq!(
+        server.get_property::<DefUserRealm>().unwrap(),
+        "EXAMPLE.COM"
+    );
+
+    server.set_property::<DefUserRealm>("OTHER.COM").unwrap();
+    assert_eq!(server.get_property::<DefUserRealm>().unwrap(), "OTHER.COM");
+
+    server
+        .set_property::<IpLocalPort>(&"127.0.0.1:1234".parse().unwrap())
+        .unwrap();
+    assert_eq!(
+        server.get_property::<IpLocalPort>().unwrap(),
+        "127.0.0.1;1234"
+    );
+
+    let err = server.get_property::<AuthExternal>().unwrap_err();
+    assert!(matches!(err, SaslError::NotDone(_)), "{:?}", err);
+    server.set_property::<AuthExternal>("alice").unwrap();
+    assert_eq!(server.get_property::<AuthExternal>().unwrap(), "alice");
+    server.set_property::<SsfExternal>(&256).unwrap();
+    assert_eq!(server.get_property::<SsfExternal>().unwrap(), 256);
+    assert_eq!(
+        server.get_property::<SecProps>().unwrap(),
+        SecurityProperties::default()
+    );
+
+    let err = server.get_property::<MechName>().unwrap_err();
+    assert!(matches!(err, SaslError::NotDone(_)), "{:?}", err);
+    assert_eq!(
+        server.start("EXTERNAL", Some(b"")).unwrap(),
+        Step::Done(None)
+    );
+    assert_eq!(server.get_property::<MechName>().unwrap(), "EXTERNAL");
+    assert_eq!(server.get_property::<Ssf>().unwrap(), 0);
+}
+
+#[test]
+fn test_client_properties() {
+    let mut client = SaslClient::builder("test", "example.com").build().unwrap();
+    assert_eq!(client.get_property::<ServerFqdn>().unwrap(), "example.com");
+
+    // The default user realm is a server-only property.
+    let err = client.get_property::<DefUserRealm>().unwrap_err();
+    assert!(matches!(err, SaslError::BadProt(_)), "{:?}", err);
+
+    let err = client.set_property::<AuthExternal>("a\0b").unwrap_err();
+    assert!(matches!(err, SaslError::BadParam(_)), "{:?}", err);
+}
+
+#[test]
+fn test_security_properties() {
+    let props = SecurityProperties::builder()
+        .min_ssf(1)
+        .max_ssf(256)
+        .max_buf_size(65536)
+        .flags(SecurityFlags::NO_PLAINTEXT | SecurityFlags::MUTUAL_AUTH)
+        .property("a", "1")
+        .property("b", "2")
+        .build();
+    let mut server = SaslServer::builder("test")
+        .security_properties(props.clone())
+        .build()
+        .unwrap();
+    assert_eq!(server.get_property::<SecProps>().unwrap(), props);
+
+    let props = SecurityProperties::builder().max_buf_size(4096).build();
+    server.set_property::<SecProps>(&props).unwrap();
+    assert_eq!(server.get_property::<SecProps>().unwrap(), props);
+
+    // Security layers cannot be both required and disabled.
+    let props = SecurityProperties::builder().min_ssf(1).build();
+    let err = server.set_property::<SecProps>(&props).unwrap_err();
+    assert!(matches!(err, SaslError::TooWeak(_)), "{:?}", err);
+    let props = SecurityProperties::builder().property("a\0", "1").build();
+    let err = server.set_property::<SecProps>(&props).unwrap_err();
+    assert!(matches!(err, SaslError::BadParam(_)), "{:?}", err);
+}
+
+#[test]
+fn test_retained_properties() {
+    let mut client = SaslClient::builder("test", "example.com").build().unwrap();
+
+    // The handle is opaque to libsasl2, and is only dereferenced by the
+    // GSSAPI mechanism.
+    let handle = 0x5a5a as *const c_void;
+    let creds = unsafe { GssCredentials::from_raw(handle) };
+    client.set_property::<GssCreds>(&creds).unwrap();
+    assert_eq!(client.get_property::<GssCreds>().unwrap(), handle);
+
+    let request =
+        HttpRequestData::new("POST", "/login", b"user=alice".to_vec()).non_persistent(true);
+    client.set_property::<HttpRequest>(&request).unwrap();
+    // libsasl2 retains the request, so it must outlive the value passed in.
+    drop(request);
+    let raw = client.get_property::<HttpRequest>().unwrap();
+    unsafe {
+        assert_eq!(CStr::from_ptr((*raw).method).to_str().unwrap(), "POST");
+        assert_eq!(CStr::from_ptr((*raw).uri).to_str().unwrap(), "/login");
+        assert_eq!(
+            slice::from_raw_parts((*raw).entity, (*raw).elen as usize),
+            b"user=alice"
+        );
+        assert_eq!((*raw).non_persist, 1);
+    }
+
+    let request = HttpRequestData::new("GET\0", "/", vec![]);
+    let err = client.set_property::<HttpRequest>(&request).unwrap_err();
+    assert!(matches!(err, SaslError::BadParam(_)), "{:?}", err);
+}
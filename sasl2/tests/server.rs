@@ -0,0 +1,84 @@
+// Copyright Materialize, Inc. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License in the LICENSE file at the
+// root of this repository, or online at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use sasl2::connection::Connection;
+use sasl2::error::SaslError;
+use sasl2::property::AuthExternal;
+use sasl2::server::{SaslServer, Step};
+use sasl2::session::ChannelBindingDisposition;
+
+#[test]
+fn test_server_external() {
+    let mut server = SaslServer::builder("test")
+        .server_fqdn("localhost")
+        .success_data(true)
+        .build()
+        .unwrap();
+
+    let err = server.start("EXTERNAL", Some(b"")).unwrap_err();
+    assert!(matches!(err, SaslError::NoMech(_)), "{:?}", err);
+
+    server.set_property::<AuthExternal>("alice").unwrap();
+
+    assert_eq!(server.username(), None);
+    let err = server.session_info().unwrap_err();
+    assert!(matches!(err, SaslError::NotDone(_)), "{:?}", err);
+    assert_eq!(
+        server.start("EXTERNAL", Some(b"")).unwrap(),
+        Step::Done(None)
+    );
+    assert!(server.is_complete());
+    assert_eq!(server.username().as_deref(), Some("alice"));
+    assert_eq!(server.auth_user().as_deref(), Some("alice"));
+    assert_eq!(server.mechanism().as_deref(), Some("EXTERNAL"));
+    assert_eq!(server.ssf(), Some(0));
+
+    let info = server.session_info().unwrap();
+    assert_eq!(info.mechanism(), "EXTERNAL");
+    assert_eq!(info.username(), Some("alice"));
+    assert_eq!(info.auth_user(), Some("alice"));
+    assert_eq!(info.ssf(), 0);
+    assert_eq!(info.channel_binding(), ChannelBindingDisposition::Absent);
+    assert_eq!(info.channel_binding_name(), None);
+}
+
+#[test]
+fn test_server_external_no_initial_response() {
+    let mut server = SaslServer::builder("test").build().unwrap();
+    server.set_property::<AuthExternal>("alice").unwrap();
+
+    assert_eq!(
+        server.start("EXTERNAL", None).unwrap(),
+        Step::Continue(vec![])
+    );
+    assert!(!server.is_complete());
+    assert_eq!(server.step(b"").unwrap(), Step::Done(None));
+    assert_eq!(server.username().as_deref(), Some("alice"));
+}
+
+#[test]
+#[cfg(feature = "plain")]
+fn test_server_plain_no_verifier() {
+    let mut server = SaslServer::builder("test").build().unwrap();
+    let err = server.start("PLAIN", Some(b"\0alice\0secret")).unwrap_err();
+    // Without a password backend, libsasl2 considers PLAIN unusable.
+    assert!(matches!(err, SaslError::NoMech(_)), "{:?}", err);
+    assert!(err
+        .detail()
+        .unwrap()
+        .contains("Password verification failed"));
+    assert!(!server.is_complete());
+    assert_eq!(server.username(), None);
+}
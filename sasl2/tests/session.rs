@@ -0,0 +1,62 @@
+// Copyright Materialize, Inc. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License in the LICENSE file at the
+// root of this repository, or online at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use sasl2::connection::Connection;
+use sasl2::error::SaslError;
+use sasl2::property::{AuthUser, MaxOutBuf, MechName, Ssf, Username};
+use sasl2::server::SaslServer;
+use sasl2::session::ChannelBindingDisposition;
+
+mod common;
+
+/// Checks the session information of `conn` against the properties that
+/// expose the same fields, and against libsasl2's output parameters, which
+/// it reads through the value of one of them.
+fn check_session_info<C>(conn: &C)
+where
+    C: Connection,
+{
+    let info = conn.session_info().unwrap();
+    assert_eq!(info.mechanism(), conn.get_property::<MechName>().unwrap());
+    assert_eq!(
+        info.username(),
+        Some(&*conn.get_property::<Username>().unwrap())
+    );
+    assert_eq!(
+        info.auth_user(),
+        Some(&*conn.get_property::<AuthUser>().unwrap())
+    );
+    assert_eq!(info.ssf(), conn.get_property::<Ssf>().unwrap());
+    assert_eq!(
+        info.max_out_buf(),
+        conn.get_property::<MaxOutBuf>().unwrap()
+    );
+    assert_eq!(info.channel_binding(), ChannelBindingDisposition::Absent);
+    assert_eq!(info.channel_binding_name(), None);
+}
+
+#[test]
+fn test_session_info() {
+    let (client, server) = common::authenticate();
+    check_session_info(&client);
+    check_session_info(&server);
+}
+
+#[test]
+fn test_session_info_not_done() {
+    let server = SaslServer::builder("test").build().unwrap();
+    let err = server.session_info().unwrap_err();
+    assert!(matches!(err, SaslError::NotDone(_)), "{:?}", err);
+}
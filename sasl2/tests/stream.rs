@@ -0,0 +1,64 @@
+// Copyright Materialize, Inc. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License in the LICENSE file at the
+// root of this repository, or online at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::io::{Cursor, IoSlice, Read, Write};
+
+use sasl2::connection::Connection;
+use sasl2::property::SecProps;
+use sasl2::security::SecurityProperties;
+use sasl2::stream::SaslStream;
+
+mod common;
+
+#[test]
+fn test_stream_passthrough() {
+    let (client, server) = common::authenticate();
+
+    let mut client = SaslStream::new(client, vec![]).unwrap();
+    assert!(!client.is_protected());
+    client.write_all(b"hello, ").unwrap();
+    client.write_all(b"world").unwrap();
+    client.flush().unwrap();
+    let (_, wire) = client.into_inner();
+    assert_eq!(wire, b"hello, world");
+
+    let mut server = SaslStream::new(server, Cursor::new(wire)).unwrap();
+    assert!(!server.is_protected());
+    let mut buf = String::new();
+    server.read_to_string(&mut buf).unwrap();
+    assert_eq!(buf, "hello, world");
+}
+
+#[test]
+fn test_encode_vectored() {
+    let (mut client, _) = common::authenticate();
+
+    // Without a security layer, encoding is a copy, but libsasl2 still
+    // requires that the application declare support for security layers.
+    let props = SecurityProperties::builder().max_buf_size(4096).build();
+    client.set_property::<SecProps>(&props).unwrap();
+
+    let bufs = [
+        IoSlice::new(b"header: "),
+        IoSlice::new(b""),
+        IoSlice::new(b"payload"),
+    ];
+    assert_eq!(client.encode_vectored(&bufs).unwrap(), b"header: payload");
+    assert_eq!(
+        client.encode(b"header: payload").unwrap(),
+        b"header: payload"
+    );
+    assert_eq!(client.encode_vectored(&[]).unwrap(), b"");
+}
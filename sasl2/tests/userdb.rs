@@ -0,0 +1,209 @@
+// Copyright Materialize, Inc. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License in the LICENSE file at the
+// root of this repository, or online at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use sasl2::userdb;
+
+#[test]
+fn test_constant_time_eq() {
+    assert!(userdb::constant_time_eq(b"", b""));
+    assert!(userdb::constant_time_eq(b"secret", b"secret"));
+    assert!(!userdb::constant_time_eq(b"secret", b"secreT"));
+    assert!(!userdb::constant_time_eq(b"secret", b"secret2"));
+}
+
+#[test]
+#[cfg(feature = "plain")]
+fn test_password_verifier() {
+    use sasl2::auxprop::AuxProps;
+    use sasl2::callbacks::{Callbacks, ConnectionInfo};
+    use sasl2::error::SaslError;
+    use sasl2::server::{SaslServer, Step};
+    use sasl2::userdb::{AuthFailure, PasswordVerifier};
+
+    struct Users;
+
+    impl PasswordVerifier for Users {
+        fn check(
+            &self,
+            conn: &ConnectionInfo,
+            user: &str,
+            password: &[u8],
+            props: &mut AuxProps,
+        ) -> Result<(), AuthFailure> {
+            assert_eq!(conn.service().as_deref(), Some("test"));
+            assert_eq!(conn.mechanism().as_deref(), Some("PLAIN"));
+            match user {
+                "alice" if userdb::constant_time_eq(password, b"secret") => {
+                    props.set("*mail", "alice@example.com").unwrap();
+                    Ok(())
+                }
+                "alice" => Err(AuthFailure::BadPassword),
+                _ => Err(AuthFailure::NoUser),
+            }
+        }
+    }
+
+    let server = || {
+        let mut server = SaslServer::builder("test")
+            .callbacks(Callbacks::new().password_verifier(Users))
+            .build()
+            .unwrap();
+        server.request_aux_properties(&["*mail"]).unwrap();
+        server
+    };
+
+    let mut s = server();
+    assert_eq!(
+        s.start("PLAIN", Some(b"\0alice\0secret")).unwrap(),
+        Step::Done(None)
+    );
+    assert_eq!(s.username().as_deref(), Some("alice"));
+    assert_eq!(
+        s.aux_properties().get("*mail"),
+        Some(vec!["alice@example.com".into()])
+    );
+
+    let mut s = server();
+    let err = s.start("PLAIN", Some(b"\0alice\0wrong")).unwrap_err();
+    assert!(matches!(err, SaslError::BadAuth(_)), "{:?}", err);
+    assert!(!s.is_complete());
+
+    let mut s = server();
+    let err = s.start("PLAIN", Some(b"\0bob\0secret")).unwrap_err();
+    assert!(matches!(err, SaslError::NoUser(_)), "{:?}", err);
+    assert!(!s.is_complete());
+}
+
+#[test]
+fn test_password_store() {
+    use std::collections::HashMap;
+    use std::sync::{Arc, Mutex};
+
+    use sasl2::auxprop::AuxProps;
+    use sasl2::callbacks::{Callbacks, ConnectionInfo};
+    use sasl2::config::SaslConfig;
+    use sasl2::error::SaslError;
+    use sasl2::server::SaslServer;
+    use sasl2::userdb::{PasswordStore, SetPasswordFailure, SetPasswordFlags};
+
+    #[derive(Clone, Default)]
+    struct Users(Arc<Mutex<HashMap<String, Option<Vec<u8>>>>>);
+
+    impl PasswordStore for Users {
+        fn set(
+            &self,
+            _conn: &ConnectionInfo,
+            user: &str,
+            password: Option<&[u8]>,
+            _props: &mut AuxProps,
+            flags: SetPasswordFlags,
+        ) -> Result<(), SetPasswordFailure> {
+            let mut users = self.0.lock().unwrap();
+            if !users.contains_key(user) && !flags.contains(SetPasswordFlags::CREATE) {
+                return Err(SetPasswordFailure::NoUser);
+            }
+            if password.is_some_and(|p| p.len() < 4) {
+                return Err(SetPasswordFailure::WeakPassword);
+            }
+            users.insert(user.into(), password.map(Into::into));
+            Ok(())
+        }
+    }
+
+    let users = Users::default();
+    // Keep mechanisms that store their own verifiers, like SCRAM, out of the
+    // way.
+    let mut server = SaslServer::builder("test")
+        .callbacks(
+            Callbacks::new()
+                .config(SaslConfig::new().mech_list(["EXTERNAL"]))
+                .password_store(users.clone()),
+        )
+        .build()
+        .unwrap();
+
+    let err = server
+        .set_password("alice", b"secret", None, SetPasswordFlags::empty())
+        .unwrap_err();
+    assert!(matches!(err, SaslError::NoUser(_)), "{:?}", err);
+    let err = server
+        .set_password("alice", b"abc", None, SetPasswordFlags::CREATE)
+        .unwrap_err();
+    assert!(matches!(err, SaslError::WeakPass(_)), "{:?}", err);
+
+    server
+        .set_password("alice", b"secret", None, SetPasswordFlags::CREATE)
+        .unwrap();
+    assert_eq!(
+        users.0.lock().unwrap()["alice"].as_deref(),
+        Some(&b"secret"[..])
+    );
+    server
+        .set_password("alice", b"", None, SetPasswordFlags::DISABLE)
+        .unwrap();
+    assert_eq!(users.0.lock().unwrap()["alice"], None);
+
+    let err = server
+        .set_password(
+            "alice",
+            b"secret",
+            None,
+            SetPasswordFlags::CREATE | SetPasswordFlags::DISABLE,
+        )
+        .unwrap_err();
+    assert!(matches!(err, SaslError::BadParam(_)), "{:?}", err);
+}
+
+#[test]
+fn test_check_password() {
+    use sasl2::auxprop::AuxProps;
+    use sasl2::callbacks::{Callbacks, ConnectionInfo};
+    use sasl2::error::SaslError;
+    use sasl2::server::SaslServer;
+    use sasl2::userdb::{AuthFailure, PasswordVerifier};
+
+    struct Users;
+
+    impl PasswordVerifier for Users {
+        fn check(
+            &self,
+            _conn: &ConnectionInfo,
+            user: &str,
+            password: &[u8],
+            _props: &mut AuxProps,
+        ) -> Result<(), AuthFailure> {
+            match user {
+                "alice" if userdb::constant_time_eq(password, b"secret") => Ok(()),
+                "alice" => Err(AuthFailure::BadPassword),
+                _ => Err(AuthFailure::NoUser),
+            }
+        }
+    }
+
+    let mut server = SaslServer::builder("test")
+        .callbacks(Callbacks::new().password_verifier(Users))
+        .build()
+        .unwrap();
+    server.check_password("alice", b"secret").unwrap();
+    let err = server.check_password("alice", b"wrong").unwrap_err();
+    assert!(matches!(err, SaslError::BadAuth(_)), "{:?}", err);
+    let err = server.check_password("bob", b"secret").unwrap_err();
+    assert!(matches!(err, SaslError::NoUser(_)), "{:?}", err);
+
+    // The verifier is not consulted, and the fallback method cannot look up
+    // any users.
+    let err = server.user_exists(None, None, "alice").unwrap_err();
+    assert!(matches!(err, SaslError::NoMech(_)), "{:?}", err);
+}